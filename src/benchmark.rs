@@ -35,3 +35,32 @@ pub fn send_benchmark_commands(benchmark: BenchmarkCommands) -> String {
     println!("{}", to_ret);
     to_ret
 }
+
+/// Prints and returns the `benchmark` serialized for reading rather than for
+/// the `TFBToolset`.
+pub fn print_benchmark_commands(benchmark: &BenchmarkCommands) -> String {
+    let to_ret = serde_json::to_string_pretty(benchmark).unwrap();
+    println!("{}", to_ret);
+    to_ret
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::benchmark::{print_benchmark_commands, BenchmarkCommands};
+    use serde_json::Value;
+
+    #[test]
+    fn it_can_print_benchmark_commands() {
+        let printed = print_benchmark_commands(&BenchmarkCommands {
+            primer_command: vec!["wrk".to_string(), "-c".to_string(), "8".to_string()],
+            ..BenchmarkCommands::default()
+        });
+        let json = serde_json::from_str::<Value>(&printed).unwrap();
+        assert_eq!(json["primer_command"][2], "8");
+        assert!(printed.contains('\n'));
+    }
+}
@@ -2,30 +2,99 @@
 //! verifier might need.
 
 mod mongodb;
+mod mssql;
 pub(crate) mod mysql;
 mod postgres;
+#[cfg(test)]
+pub(crate) mod stub;
 
+use crate::config;
 use crate::database::mongodb::Mongodb;
+use crate::database::mssql::Mssql;
 use crate::database::mysql::Mysql;
-use crate::database::postgres::Postgres;
-use crate::error::VerifierError::InvalidDatabaseType;
-use crate::error::VerifierResult;
-use crate::request::request;
+use crate::database::postgres::{Flavor, Postgres};
+use crate::error::VerifierError::{
+    DatabaseUnreachable, InsufficientDatabasePrivileges, InvalidDatabaseType,
+};
+use crate::error::{VerifierError, VerifierResult};
+use crate::logger::{log, LogLevel, LogOptions};
+use crate::request::Request;
 use crate::verification::Messages;
+use colored::Colorize;
+use rand::Rng;
 use std::cmp;
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use strum_macros::EnumString;
 use threadpool::ThreadPool;
 
+/// The longest a single `ping` may take while waiting for the database.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often progress is logged while waiting for the database, so that a
+/// slow start does not look like a hang.
+const WAIT_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The failed requests, with none succeeding, after which a burst is
+/// abandoned; the server is taken to be down.
+const CIRCUIT_BREAKER_FAILURES: u32 = 20;
+
+/// The columns (or document fields) which the test types read, by table, and
+/// the type each must have.
+const EXPECTED_COLUMNS: [(&str, &str, ColumnType); 4] = [
+    ("world", "id", ColumnType::Integer),
+    ("world", "randomnumber", ColumnType::Integer),
+    ("fortune", "id", ColumnType::Integer),
+    ("fortune", "message", ColumnType::Text),
+];
+
+/// The type of a column, as far as the test types are concerned; any other
+/// type is kept by the name the database gave it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Text,
+    Other(String),
+}
+impl ColumnType {
+    /// Gets the `ColumnType` of the SQL `data_type`, as named by the
+    /// `information_schema.columns` of Postgres, MySQL or SQL Server.
+    pub fn from_sql(data_type: &str) -> ColumnType {
+        match data_type.to_lowercase().as_str() {
+            "integer" | "int" | "smallint" | "tinyint" | "mediumint" | "bigint" => {
+                ColumnType::Integer
+            }
+            "text" | "character varying" | "character" | "varchar" | "char" | "tinytext"
+            | "mediumtext" | "longtext" | "nvarchar" | "nchar" | "ntext" => ColumnType::Text,
+            other => ColumnType::Other(other.to_string()),
+        }
+    }
+}
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnType::Integer => write!(f, "an integer"),
+            ColumnType::Text => write!(f, "text"),
+            ColumnType::Other(data_type) => write!(f, "{}", data_type),
+        }
+    }
+}
+
 #[derive(EnumString, Debug)]
 #[strum(serialize_all = "lowercase")]
 pub enum Database {
     Mysql,
     Postgres,
     Mongodb,
+    /// CockroachDB, through the Postgres driver.
+    Cockroach,
+    /// Microsoft SQL Server.
+    Mssql,
 }
 impl Database {
     /// Gets a `Box`ed `DatabaseVerifier` for the given `database_name`.
@@ -33,8 +102,10 @@ impl Database {
         if let Ok(database_type) = Database::from_str(&database_name.to_lowercase()) {
             return match database_type {
                 Database::Mysql => Ok(Box::new(Mysql {})),
-                Database::Postgres => Ok(Box::new(Postgres {})),
+                Database::Postgres => Ok(Box::new(Postgres::new(Flavor::Postgres))),
                 Database::Mongodb => Ok(Box::new(Mongodb {})),
+                Database::Cockroach => Ok(Box::new(Postgres::new(Flavor::Cockroach))),
+                Database::Mssql => Ok(Box::new(Mssql {})),
             };
         } else {
             let mut messages = Messages::default();
@@ -45,6 +116,21 @@ impl Database {
         }
         Err(InvalidDatabaseType(database_name.to_string()))
     }
+
+    /// Waits for the database named `database_name` to accept connections and
+    /// checks that it answers a trivial query. Needs no test type; this is
+    /// all that `MODE=database` requires.
+    pub fn health_check(database_name: &str) -> Messages {
+        let mut messages = Messages::default();
+        match Database::get(database_name) {
+            Ok(database) => check_health(database.as_ref(), &mut messages),
+            Err(_) => messages.error(
+                format!("Invalid database type: {}", database_name),
+                "Invalid Database",
+            ),
+        }
+        messages
+    }
 }
 
 /// Trait for interfacing with any type of database.
@@ -53,31 +139,54 @@ impl Database {
 /// order to provide additional test type implementations. Not all underlying
 /// database types will have analogues for every type of action that may be
 /// required.
+///
+/// The `verify_*_count` checks must run one at a time, with no other requests
+/// to the framework in flight: each compares statistics read before and after
+/// its own burst, and those statistics are shared (`pg_stat_statements` on
+/// Postgres, `crdb_internal.node_statement_statistics` on CockroachDB, the
+/// global `Com_*`/`Innodb_rows_*` counters on MySQL, `opcounters` on
+/// MongoDB, and `sys.dm_exec_query_stats` on SQL Server), so any overlapping
+/// request would be counted too.
 pub trait DatabaseInterface {
     /// Checks that the number of queries issued by the application after
-    /// requesting `url` a known number of times (given by
+    /// sending `request` a known number of times (given by
     /// `concurrency` * `repetitions`) are equal.
     fn verify_queries_count(
         &self,
-        url: &str,
+        request: &Request,
         table_name: &str,
         concurrency: u32,
         repetitions: u32,
         expected_queries: u32,
         messages: &mut Messages,
     ) {
-        let all_queries_before_count = self.get_count_of_all_queries_for_table(table_name);
+        let all_queries_before_count = match self.get_count_of_all_queries_for_table(table_name) {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
 
-        self.issue_multi_query_requests(url, concurrency, repetitions, messages);
+        if self.issue_multi_query_requests(request, concurrency, repetitions, messages) {
+            return;
+        }
 
-        let all_queries_after_count = self.get_count_of_all_queries_for_table(table_name);
+        let all_queries_after_count = match self.get_count_of_all_queries_for_table(table_name) {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
 
         let queries = all_queries_after_count - all_queries_before_count;
         // Note: Some database implementations are less accurate (though still
         // precise) than others, and sometimes over-report rows updated. We do
         // not warn because it would just be noisy over something out of the
         // implementer's control.
-        if let cmp::Ordering::Less = queries.cmp(&expected_queries) {
+        let tolerance = config::query_count_tolerance();
+        if let cmp::Ordering::Less = queries.cmp(&tolerated_minimum(expected_queries, tolerance)) {
             messages.error(
                 format!(
                     "Only {} executed queries in the database out of at least {} expected.",
@@ -89,11 +198,11 @@ pub trait DatabaseInterface {
     }
 
     /// Checks that the number of rows that were selected by the application
-    /// after requesting `url` a known number of times (given by
+    /// after sending `request` a known number of times (given by
     /// `concurrency` * `repetitions`) are equal.
     fn verify_rows_count(
         &self,
-        url: &str,
+        request: &Request,
         table_name: &str,
         concurrency: u32,
         repetitions: u32,
@@ -102,19 +211,34 @@ pub trait DatabaseInterface {
         messages: &mut Messages,
     ) {
         let all_rows_selected_before_count =
-            self.get_count_of_rows_selected_for_table(table_name, expected_rows_per_query);
+            match self.get_count_of_rows_selected_for_table(table_name, expected_rows_per_query) {
+                Ok(count) => count,
+                Err(e) => {
+                    report_counting_error(e, messages);
+                    return;
+                }
+            };
 
-        self.issue_multi_query_requests(url, concurrency, repetitions, messages);
+        if self.issue_multi_query_requests(request, concurrency, repetitions, messages) {
+            return;
+        }
 
         let all_rows_selected_after_count =
-            self.get_count_of_rows_selected_for_table(table_name, expected_rows_per_query);
+            match self.get_count_of_rows_selected_for_table(table_name, expected_rows_per_query) {
+                Ok(count) => count,
+                Err(e) => {
+                    report_counting_error(e, messages);
+                    return;
+                }
+            };
 
         let rows = all_rows_selected_after_count - all_rows_selected_before_count;
         // Note: Some database implementations are less accurate (though still
         // precise) than others, and sometimes over-report rows updated. We do
         // not warn because it would just be noisy over something out of the
         // implementer's control.
-        if let cmp::Ordering::Less = rows.cmp(&expected_rows) {
+        let tolerance = config::query_count_tolerance();
+        if let cmp::Ordering::Less = rows.cmp(&tolerated_minimum(expected_rows, tolerance)) {
             messages.error(
                 format!(
                     "Only {} executed rows read in the database out of roughly {} expected.",
@@ -125,89 +249,202 @@ pub trait DatabaseInterface {
         };
     }
 
-    /// Issues `concurrency` requests to `url` exactly `repetition + 1` times
+    /// Checks that the application answers `request`, sent a known number of
+    /// times (given by `concurrency` * `repetitions`), mostly from a cache,
+    /// rather than issuing the `uncached_queries` it would without one.
+    ///
+    /// Note: the requests are sent once beforehand without counting, because
+    /// a cache may legitimately be populated by the first request for a row.
+    fn verify_queries_are_cached(
+        &self,
+        request: &Request,
+        table_name: &str,
+        concurrency: u32,
+        repetitions: u32,
+        uncached_queries: u32,
+        messages: &mut Messages,
+    ) {
+        if self.issue_multi_query_requests(request, concurrency, repetitions, messages) {
+            return;
+        }
+
+        let all_queries_before_count = match self.get_count_of_all_queries_for_table(table_name) {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
+
+        if self.issue_multi_query_requests(request, concurrency, repetitions, messages) {
+            return;
+        }
+
+        let all_queries_after_count = match self.get_count_of_all_queries_for_table(table_name) {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
+
+        report_uncached_queries(
+            all_queries_after_count.saturating_sub(all_queries_before_count),
+            uncached_queries,
+            messages,
+        );
+    }
+
+    /// Sends `concurrency` copies of `request` exactly `repetition + 1` times
     /// in a concurrent fashion.
     ///
     /// In practice, this means that this function will spawn as many threads
-    /// as cores are available, and each thread is going to issue a request to
-    /// `url` in a loop over there being more requests to send while decreasing
-    /// the number of requests to send on every iteration atomically, and
-    /// blocks until all the threads have completed their work.
+    /// as cores are available, divide the `concurrency` requests of each
+    /// repetition between them as evenly as possible, and block until all the
+    /// threads have sent their share.
+    ///
+    /// For example, on a dual-core machine with a `concurrency` of 512, this
+    /// function will spawn 2 threads each of which will make exactly 256
+    /// requests to `url`, counting the successful and failed ones.
+    ///
+    /// Each thread sleeps for a random duration of up to `REQUEST_JITTER_MS`
+    /// between its requests so that the threads do not all connect to `url`
+    /// in lockstep.
     ///
-    /// For example, on a dual-core machine, this function will spawn 2 threads
-    /// each of which will make a request to `url`, increment an atomic counter
-    /// of successful or failed requests, decrement the shared remaining
-    /// requests atomic counter, and loop until that counter has run out. At
-    /// the end of this example, it is expected that each thread will have run
-    /// 256 times (on average).
+    /// Should the first `CIRCUIT_BREAKER_FAILURES` requests all fail, the
+    /// remaining requests (and repetitions) are not sent, and that alone is
+    /// reported. Returns whether the requests were aborted so, in which case
+    /// the database has nothing worth counting.
     fn issue_multi_query_requests(
         &self,
-        url: &str,
+        request: &Request,
         concurrency: u32,
         repetitions: u32,
         messages: &mut Messages,
-    ) {
-        let transaction_failures = Arc::new(AtomicU32::new(0));
-        let transaction_successes = Arc::new(AtomicU32::new(0));
+    ) -> bool {
+        let jitter = config::request_jitter();
+        let latencies = Arc::new(Mutex::new(Vec::new()));
+        let mut successes = 0;
+        let mut failures = 0;
         for _ in 0..repetitions {
-            let requests_to_send = Arc::new(AtomicU32::new(concurrency));
-            let pool = ThreadPool::new(num_cpus::get());
-
-            for _ in 0..num_cpus::get() {
-                let url = url.to_string();
-                let transaction_failures = Arc::clone(&transaction_failures);
-                let transaction_successes = Arc::clone(&transaction_successes);
-                let requests = Arc::clone(&requests_to_send);
-                pool.execute(move || loop {
-                    match request(&*url) {
-                        Ok(_) => {
-                            if requests.load(Ordering::SeqCst) > 0 {
-                                requests.fetch_sub(1, Ordering::SeqCst);
-                                transaction_successes.fetch_add(1, Ordering::SeqCst);
-                            } else {
-                                break;
-                            }
-                        }
-                        Err(_) => {
-                            if requests.load(Ordering::SeqCst) > 0 {
-                                requests.fetch_sub(1, Ordering::SeqCst);
-                                transaction_failures.fetch_add(1, Ordering::SeqCst);
-                            } else {
-                                break;
-                            }
-                        }
-                    };
+            let to_send = request.clone();
+            let recorded = Arc::clone(&latencies);
+            let (succeeded, failed) =
+                send_concurrently(concurrency, num_cpus::get(), jitter, move || match to_send
+                    .fetch()
+                {
+                    Ok(response) => {
+                        recorded.lock().unwrap().push(response.total_time);
+                        true
+                    }
+                    Err(_) => false,
                 });
+            successes += succeeded;
+            failures += failed;
+            if succeeded + failed < concurrency {
+                messages.latencies(std::mem::take(&mut *latencies.lock().unwrap()));
+                messages.error(
+                    format!(
+                        "Server at {} appears down; aborted after {} consecutive failures",
+                        request.url, failed
+                    ),
+                    "Server Down",
+                );
+                return true;
             }
-            pool.join();
         }
+        messages.latencies(std::mem::take(&mut *latencies.lock().unwrap()));
 
-        let failures = transaction_failures.load(Ordering::SeqCst);
         if failures > 0 {
             messages.error(
-                format!("Failed response(s) from {}: {}", url, failures),
+                format!("Failed response(s) from {}: {}", request.url, failures),
                 "Failed Response",
             );
         }
-        let successes = transaction_successes.load(Ordering::SeqCst);
         if successes != concurrency * repetitions {
             messages.error(
                 format!(
                     "Unexpected response count from {}: {}; expected: {}",
-                    url,
+                    request.url,
                     successes,
                     concurrency * repetitions
                 ),
                 "Unexpected Responses",
             );
         }
+
+        false
+    }
+
+    /// Blocks until the database answers a `ping`, and returns whether it did;
+    /// reports an error to `messages` if it does not within
+    /// `config::database_wait_secs`.
+    fn wait_for_database_to_be_available(&self, messages: &mut Messages) -> bool {
+        wait_until(
+            config::database_wait_secs(),
+            &|timeout| self.ping(timeout),
+            messages,
+        )
     }
 
-    fn wait_for_database_to_be_available(&self);
+    /// Runs a trivial query (e.g. `SELECT 1`) and returns whether the
+    /// database answered it; connecting and answering may take at most
+    /// `timeout`, so that an unreachable database fails fast rather than
+    /// waiting out the driver's own (often much longer) timeouts.
+    fn ping(&self, timeout: Duration) -> bool;
 
     /// Gets all of the `world` table (or analogue) entries from the underlying
     /// database and returns them as a map from `id` to `randomnumber`.
-    fn get_all_from_world_table(&self) -> HashMap<i32, i32>;
+    fn get_all_from_world_table(&self, messages: &mut Messages) -> HashMap<i32, i32>;
+
+    /// Counts the rows of the `world` table (or analogue), or errors if the
+    /// database could not be asked.
+    fn count_world_rows(&self) -> VerifierResult<i64>;
+
+    /// Warns unless the `world` table holds exactly `config::world_row_count`
+    /// (10,000 by default) rows, as the query test types presuppose; otherwise
+    /// an environment seeded with the wrong rows would pass the `id` range
+    /// checks unnoticed.
+    fn verify_world_row_count(&self, messages: &mut Messages) {
+        let expected_rows = config::world_row_count();
+        match self.count_world_rows() {
+            Ok(rows) if rows != expected_rows => messages.warning(
+                format!(
+                    "The world table has {} rows; expected exactly {}. Check the database setup.",
+                    rows, expected_rows
+                ),
+                "World Row Count",
+            ),
+            Ok(_) => {}
+            Err(e) => messages.warning(
+                format!("Could not count the rows of the world table: {}", e),
+                "World Row Count Unverified",
+            ),
+        }
+    }
+
+    /// Gets the type of each column of `table_name` (or of the fields of one
+    /// of its documents), keyed by the lowercased column name.
+    fn get_column_types(&self, table_name: &str) -> VerifierResult<HashMap<String, ColumnType>>;
+
+    /// Warns about any column which the test types read but which is missing
+    /// from the `world` and `fortune` tables, or is not of the expected type;
+    /// e.g. a `randomnumber` stored as a string would still be served as the
+    /// right JSON, but violates the specification.
+    fn verify_schema(&self, messages: &mut Messages) {
+        for table_name in ["world", "fortune"] {
+            match self.get_column_types(table_name) {
+                Ok(columns) => report_schema(table_name, &columns, messages),
+                Err(e) => messages.warning(
+                    format!(
+                        "Could not read the schema of the {} table: {}",
+                        table_name, e
+                    ),
+                    "Schema Unverified",
+                ),
+            }
+        }
+    }
 
     /// Inserts 1,000 static fortunes into the `fortune` table (or analogue).
     ///
@@ -222,21 +459,284 @@ pub trait DatabaseInterface {
     ///
     /// Put bluntly, this action is safe because the *next* opportunity
     /// for something to read the unaltered fortunes table **must** restart the
-    /// database container.
-    fn insert_one_thousand_fortunes(&self);
+    /// database container - unless `reset_fortune_table` is called after.
+    fn insert_one_thousand_fortunes(&self, messages: &mut Messages);
+
+    /// Removes the fortunes added by `insert_one_thousand_fortunes` (those
+    /// with an `id` of 13 or greater), restoring the `fortune` table to its
+    /// original 12 rows.
+    ///
+    /// Note: this is only required when verifying repeatedly against a
+    /// database which is *not* restarted in between; e.g. one running locally.
+    fn reset_fortune_table(&self, _messages: &mut Messages) {}
 
     /// Gets the count of all queries run against the given `table_name`.
-    fn get_count_of_all_queries_for_table(&self, table_name: &str) -> u32;
+    ///
+    /// Returns `InsufficientDatabasePrivileges` if the database refused to
+    /// let us read its statistics.
+    fn get_count_of_all_queries_for_table(&self, table_name: &str) -> VerifierResult<u32>;
 
     /// Gets the count of all rows selected for the given `table_name`.
-    fn get_count_of_rows_selected_for_table(&self, table_name: &str, rows_per_query: u32) -> u32;
+    fn get_count_of_rows_selected_for_table(
+        &self,
+        table_name: &str,
+        rows_per_query: u32,
+    ) -> VerifierResult<u32>;
 
     /// Gets the count of all rows updated for the given `table_name`.
     fn get_count_of_rows_updated_for_table(
         &self,
         table_name: &str,
         expected_rows_per_query: u32,
-    ) -> u32;
+    ) -> VerifierResult<u32>;
+}
+
+/// Gets the client from `connection`, reporting to `messages` why the database
+/// could not be connected to otherwise; callers would only see missing rows or
+/// zero counts.
+pub(crate) fn connected<T>(connection: VerifierResult<T>, messages: &mut Messages) -> Option<T> {
+    match connection {
+        Ok(client) => Some(client),
+        Err(e) => {
+            messages.error(
+                format!("Could not connect to the database: {}", e),
+                "Database connection error",
+            );
+            None
+        }
+    }
+}
+
+/// Reports an error which occurred while reading the database statistics
+/// used for counting queries, rows selected, and rows updated.
+///
+/// Note: this is reported *instead of* the counting checks themselves; a
+/// permission error would otherwise surface as a misleading "Too Few Queries".
+pub(crate) fn report_counting_error(error: VerifierError, messages: &mut Messages) {
+    match error {
+        InsufficientDatabasePrivileges(detail) => messages.error(
+            format!(
+                "Insufficient database privileges to read statistics: {}",
+                detail
+            ),
+            "Insufficient Privileges",
+        ),
+        DatabaseUnreachable(detail) => messages.error(
+            format!(
+                "Could not reach database while counting queries: {}",
+                detail
+            ),
+            "Database Unreachable",
+        ),
+        _ => messages.error(
+            format!("Could not read database statistics: {:?}", error),
+            "Database Error",
+        ),
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Reports, for `table_name`, each `EXPECTED_COLUMNS` entry which is missing
+/// from `columns` or is of another type.
+fn report_schema(table_name: &str, columns: &HashMap<String, ColumnType>, messages: &mut Messages) {
+    for (_, column, expected) in EXPECTED_COLUMNS
+        .iter()
+        .filter(|(table, _, _)| *table == table_name)
+    {
+        match columns.get(*column) {
+            None => messages.warning(
+                format!("The {} table has no {} column.", table_name, column),
+                "Schema Mismatch",
+            ),
+            Some(actual) if actual != expected => messages.warning(
+                format!(
+                    "The {}.{} column is {}; expected {}.",
+                    table_name, column, actual, expected
+                ),
+                "Schema Mismatch",
+            ),
+            Some(_) => {}
+        }
+    }
+}
+
+/// Reports `queries` executed where `uncached_queries` would be without a
+/// cache. Once populated, a cache answers nearly every request, so anything
+/// approaching a query per lookup means the cache is not being used.
+fn report_uncached_queries(queries: u32, uncached_queries: u32, messages: &mut Messages) {
+    if queries >= uncached_queries / 2 {
+        messages.error(
+            format!(
+                "{} executed queries in the database out of {} expected without a cache; cached queries should rarely reach the database.",
+                queries, uncached_queries
+            ),
+            "Cache Not Used",
+        );
+    }
+}
+
+/// Waits for `database` to become available, then reports an error unless it
+/// answers a `ping`; it is not pinged if the wait already failed.
+fn check_health(database: &dyn DatabaseInterface, messages: &mut Messages) {
+    if !database.wait_for_database_to_be_available(messages) {
+        return;
+    }
+    if !database.ping(PING_TIMEOUT) {
+        messages.error(
+            "Database is accepting connections but did not answer a trivial query.",
+            "Database unhealthy",
+        );
+    }
+}
+
+/// Pings via `ping` about once a second, until `max` seconds have elapsed,
+/// and returns whether it was answered; reports an error to `messages` if it
+/// was not.
+///
+/// Each attempt is given at most `PING_TIMEOUT`, and never more than what is
+/// left of `max`, so a hanging connection attempt cannot outlast the wait.
+/// Progress is logged every `WAIT_PROGRESS_INTERVAL`, and once answered.
+fn wait_until(max: u32, ping: &dyn Fn(Duration) -> bool, messages: &mut Messages) -> bool {
+    wait_until_logging(
+        max,
+        ping,
+        WAIT_PROGRESS_INTERVAL,
+        &mut log_wait_progress,
+        messages,
+    )
+}
+
+/// Waits as `wait_until` does, but passes its progress to `progress` every
+/// `progress_interval` instead of logging it.
+fn wait_until_logging(
+    max: u32,
+    ping: &dyn Fn(Duration) -> bool,
+    progress_interval: Duration,
+    progress: &mut dyn FnMut(String),
+    messages: &mut Messages,
+) -> bool {
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(max as u64);
+    let mut next_progress = started + progress_interval;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        if ping(cmp::min(PING_TIMEOUT, remaining)) {
+            progress(format!(
+                "Database available after {}s",
+                started.elapsed().as_secs()
+            ));
+            return true;
+        }
+        if Instant::now() >= next_progress {
+            progress(format!(
+                "Waiting for database... {}s/{}s",
+                started.elapsed().as_secs(),
+                max
+            ));
+            next_progress += progress_interval;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        thread::sleep(cmp::min(Duration::from_secs(1), remaining));
+    }
+    messages.error(
+        format!(
+            "Database connection could not be established after {} seconds.",
+            max
+        ),
+        "Database unavailable",
+    );
+    false
+}
+
+/// Gets the fewest of `expected` counted queries or rows which are not too
+/// few, allowing them to fall short by `tolerance_percent`; see
+/// `config::query_count_tolerance`.
+fn tolerated_minimum(expected: u32, tolerance_percent: f64) -> u32 {
+    (expected as f64 * (1.0 - tolerance_percent / 100.0)).ceil() as u32
+}
+
+/// Logs `progress` of `wait_until` at the default level.
+fn log_wait_progress(progress: String) {
+    log(
+        progress.normal(),
+        LogOptions {
+            border: None,
+            border_bottom: None,
+            level: LogLevel::Info,
+        },
+    );
+}
+
+/// Sends exactly `count` requests via `send` from a pool of `workers`
+/// threads, and returns the number of them which succeeded and failed.
+///
+/// Each worker sends its `chunk_sizes` share of the requests, and sleeps for
+/// a `jitter_delay` of up to `jitter` between them. Once
+/// `CIRCUIT_BREAKER_FAILURES` have failed without any succeeding, the workers
+/// stop; fewer than `count` are then sent.
+fn send_concurrently<F>(count: u32, workers: usize, jitter: Duration, send: F) -> (u32, u32)
+where
+    F: Fn() -> bool + Send + Sync + 'static,
+{
+    let send = Arc::new(send);
+    let successes = Arc::new(AtomicU32::new(0));
+    let failures = Arc::new(AtomicU32::new(0));
+    let pool = ThreadPool::new(workers);
+
+    for chunk in chunk_sizes(count, workers) {
+        let send = Arc::clone(&send);
+        let successes = Arc::clone(&successes);
+        let failures = Arc::clone(&failures);
+        pool.execute(move || {
+            for i in 0..chunk {
+                if successes.load(Ordering::SeqCst) == 0
+                    && failures.load(Ordering::SeqCst) >= CIRCUIT_BREAKER_FAILURES
+                {
+                    break;
+                }
+                if i > 0 {
+                    thread::sleep(jitter_delay(jitter));
+                }
+                if send() {
+                    successes.fetch_add(1, Ordering::SeqCst);
+                } else {
+                    failures.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+    }
+    pool.join();
+
+    (
+        successes.load(Ordering::SeqCst),
+        failures.load(Ordering::SeqCst),
+    )
+}
+
+/// Divides `count` between `workers` as evenly as possible; the first
+/// `count % workers` of them take one more than the rest.
+fn chunk_sizes(count: u32, workers: usize) -> Vec<u32> {
+    let workers = workers.max(1) as u32;
+    (0..workers)
+        .map(|worker| count / workers + u32::from(worker < count % workers))
+        .collect()
+}
+
+/// Gets a random duration between zero and `max`, inclusive.
+fn jitter_delay(max: Duration) -> Duration {
+    if max == Duration::from_micros(0) {
+        return max;
+    }
+    let micros = rand::thread_rng().gen_range(0..=max.as_micros() as u64);
+
+    Duration::from_micros(micros)
 }
 
 //
@@ -245,7 +745,27 @@ pub trait DatabaseInterface {
 
 #[cfg(test)]
 mod tests {
-    use crate::database::Database;
+    use crate::database::stub::Stub;
+    use crate::database::{
+        check_health, chunk_sizes, connected, jitter_delay, report_schema, report_uncached_queries,
+        send_concurrently, tolerated_minimum, wait_until, wait_until_logging, ColumnType, Database,
+        DatabaseInterface, CIRCUIT_BREAKER_FAILURES, WAIT_PROGRESS_INTERVAL,
+    };
+    use crate::error::VerifierError::DatabaseError;
+    use crate::request::Request;
+    use crate::verification::Messages;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn it_should_tolerate_counts_short_by_the_tolerance() {
+        assert_eq!(tolerated_minimum(1_024, 0.0), 1_024);
+        assert_eq!(tolerated_minimum(1_000, 2.0), 980);
+        assert_eq!(tolerated_minimum(1_001, 2.0), 981);
+        assert_eq!(tolerated_minimum(0, 2.0), 0);
+    }
 
     #[test]
     fn it_should_get_mysql() {
@@ -267,4 +787,420 @@ mod tests {
             panic!("mongodb test type broken");
         }
     }
+
+    #[test]
+    fn it_should_get_cockroach() {
+        if Database::get("cockroach").is_err() {
+            panic!("cockroach test type broken");
+        }
+    }
+
+    #[test]
+    fn it_should_get_mssql() {
+        if Database::get("mssql").is_err() {
+            panic!("mssql test type broken");
+        }
+    }
+
+    #[test]
+    fn it_should_report_insufficient_privileges() {
+        let stub = Stub {
+            deny_statistics: true,
+            ..Stub::default()
+        };
+        let mut messages = Messages::default();
+        stub.verify_queries_count(
+            &Request::get("http://tfb-server:8080/db"),
+            "world",
+            1,
+            1,
+            1,
+            &mut messages,
+        );
+
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("Insufficient database privileges to read statistics"));
+        assert!(!messages.errors[0].message.contains("executed queries"));
+    }
+
+    #[test]
+    fn it_should_send_exact_request_count_with_jitter() {
+        let sent = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&sent);
+        let (successes, failures) = send_concurrently(37, 4, Duration::from_millis(2), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            true
+        });
+
+        assert_eq!(sent.load(Ordering::SeqCst), 37);
+        assert_eq!(successes, 37);
+        assert_eq!(failures, 0);
+    }
+
+    #[test]
+    fn it_should_stop_sending_once_the_first_requests_all_fail() {
+        let sent = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&sent);
+        let (successes, failures) =
+            send_concurrently(1_000, 4, Duration::from_millis(0), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                false
+            });
+
+        assert_eq!(successes, 0);
+        assert!(failures >= CIRCUIT_BREAKER_FAILURES);
+        assert!(failures < CIRCUIT_BREAKER_FAILURES + 4);
+        assert_eq!(sent.load(Ordering::SeqCst), failures);
+    }
+
+    #[test]
+    fn it_should_report_a_server_which_appears_down() {
+        let mut messages = Messages::default();
+        let aborted = Stub::default().issue_multi_query_requests(
+            &Request::get("http://127.0.0.1:1/db"),
+            512,
+            2,
+            &mut messages,
+        );
+
+        assert!(aborted);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .starts_with("Server at http://127.0.0.1:1/db appears down; aborted after"));
+    }
+
+    #[test]
+    fn it_should_not_count_queries_once_the_server_appears_down() {
+        let mut messages = Messages::default();
+        Stub::default().verify_queries_count(
+            &Request::get("http://127.0.0.1:1/db"),
+            "world",
+            512,
+            2,
+            1024,
+            &mut messages,
+        );
+
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0].message.contains("appears down"));
+    }
+
+    #[test]
+    fn it_should_divide_requests_evenly_between_workers() {
+        assert_eq!(chunk_sizes(512, 2), vec![256, 256]);
+        assert_eq!(chunk_sizes(37, 4), vec![10, 9, 9, 9]);
+        assert_eq!(chunk_sizes(2, 4), vec![1, 1, 0, 0]);
+        assert_eq!(chunk_sizes(5, 0), vec![5]);
+    }
+
+    #[test]
+    fn it_should_apply_jitter_between_requests() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let sent = Arc::clone(&counter);
+        let start = Instant::now();
+        let (successes, failures) =
+            send_concurrently(10, 1, Duration::from_millis(20), move || {
+                sent.fetch_add(1, Ordering::SeqCst) < 5
+            });
+
+        // 9 gaps averaging 10ms; all of them being under 1ms is negligible.
+        assert!(start.elapsed() >= Duration::from_millis(9));
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+        assert_eq!(successes, 5);
+        assert_eq!(failures, 5);
+    }
+
+    #[test]
+    fn it_should_bound_jitter_delay() {
+        let max = Duration::from_millis(3);
+        for _ in 0..100 {
+            assert!(jitter_delay(max) <= max);
+        }
+        assert_eq!(
+            jitter_delay(Duration::from_millis(0)),
+            Duration::from_millis(0)
+        );
+    }
+
+    #[test]
+    fn it_should_pass_health_check_when_database_answers() {
+        let mut messages = Messages::default();
+        check_health(&Stub::default(), &mut messages);
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_fail_health_check_when_database_does_not_answer() {
+        let stub = Stub {
+            unreachable: true,
+            ..Default::default()
+        };
+        let mut messages = Messages::default();
+        check_health(&stub, &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("did not answer a trivial query"));
+    }
+
+    #[test]
+    fn it_should_not_ping_when_database_is_unavailable() {
+        let stub = Stub {
+            unavailable: true,
+            ..Default::default()
+        };
+        let calls = stub.calls.clone();
+        let mut messages = Messages::default();
+        check_health(&stub, &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("could not be established"));
+        // Only the wait itself was called.
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_should_fail_health_check_of_unknown_database() {
+        let messages = Database::health_check("oracle");
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("Invalid database type: oracle"));
+    }
+
+    #[test]
+    fn it_should_get_connected_client() {
+        let mut messages = Messages::default();
+        assert_eq!(connected::<u32>(Ok(5), &mut messages), Some(5));
+        assert!(messages.errors.is_empty());
+
+        assert_eq!(
+            connected::<u32>(
+                Err(DatabaseError("connection refused".to_string())),
+                &mut messages
+            ),
+            None
+        );
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("Could not connect to the database: "));
+    }
+
+    #[test]
+    fn it_should_report_unreachable_database_while_counting() {
+        let stub = Stub {
+            unreachable: true,
+            ..Stub::default()
+        };
+        let mut messages = Messages::default();
+        stub.verify_rows_count(
+            &Request::get("http://tfb-server:8080/db"),
+            "world",
+            1,
+            1,
+            1,
+            1,
+            &mut messages,
+        );
+
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("Could not reach database while counting queries"));
+        assert!(!messages.errors[0].message.contains("rows"));
+    }
+
+    #[test]
+    fn it_should_stop_waiting_once_available() {
+        let polls = AtomicU32::new(0);
+        let mut messages = Messages::default();
+        let available = wait_until(
+            60,
+            &|_| polls.fetch_add(1, Ordering::SeqCst) >= 1,
+            &mut messages,
+        );
+
+        assert!(available);
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_bound_each_ping_by_the_time_left_to_wait() {
+        let timeouts = Mutex::new(Vec::new());
+        let mut messages = Messages::default();
+        let available = wait_until(
+            1,
+            &|timeout| {
+                timeouts.lock().unwrap().push(timeout);
+                false
+            },
+            &mut messages,
+        );
+
+        assert!(!available);
+        let timeouts = timeouts.into_inner().unwrap();
+        assert!(!timeouts.is_empty());
+        assert!(timeouts.iter().all(|t| *t <= Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn it_should_report_database_unavailable_after_waiting() {
+        let mut messages = Messages::default();
+        assert!(!wait_until(0, &|_| false, &mut messages));
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0].message.contains("after 0 seconds"));
+    }
+
+    #[test]
+    fn it_should_log_progress_until_available() {
+        let polls = AtomicU32::new(0);
+        let mut progress = Vec::new();
+        let mut messages = Messages::default();
+        let available = wait_until_logging(
+            60,
+            &|_| polls.fetch_add(1, Ordering::SeqCst) >= 1,
+            Duration::from_secs(0),
+            &mut |line| progress.push(line),
+            &mut messages,
+        );
+
+        assert!(available);
+        assert_eq!(
+            progress,
+            vec![
+                "Waiting for database... 0s/60s",
+                "Database available after 1s"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_log_availability_without_waiting() {
+        let mut progress = Vec::new();
+        let mut messages = Messages::default();
+        wait_until_logging(
+            60,
+            &|_| true,
+            WAIT_PROGRESS_INTERVAL,
+            &mut |line| progress.push(line),
+            &mut messages,
+        );
+
+        assert_eq!(progress, vec!["Database available after 0s"]);
+    }
+
+    #[test]
+    fn it_should_log_progress_until_timed_out() {
+        let mut progress = Vec::new();
+        let mut messages = Messages::default();
+        let available = wait_until_logging(
+            1,
+            &|_| false,
+            Duration::from_secs(0),
+            &mut |line| progress.push(line),
+            &mut messages,
+        );
+
+        assert!(!available);
+        assert_eq!(progress, vec!["Waiting for database... 0s/1s"]);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("could not be established after 1 seconds"));
+    }
+
+    #[test]
+    fn it_should_report_queries_which_scale_with_requests() {
+        let mut messages = Messages::default();
+        report_uncached_queries(120, 20_480, &mut messages);
+        assert!(messages.errors.is_empty());
+
+        report_uncached_queries(20_480, 20_480, &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("20480 executed queries"));
+    }
+
+    #[test]
+    fn it_should_warn_unless_world_has_ten_thousand_rows() {
+        let seeded = Stub {
+            world: (1..=10_000).map(|id| (id, id)).collect(),
+            ..Default::default()
+        };
+        let mut messages = Messages::default();
+        seeded.verify_world_row_count(&mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let short = Stub {
+            world: (1..=9_999).map(|id| (id, id)).collect(),
+            ..Default::default()
+        };
+        short.verify_world_row_count(&mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("has 9999 rows; expected exactly 10000"));
+    }
+
+    #[test]
+    fn it_should_report_when_world_rows_cannot_be_counted() {
+        let stub = Stub {
+            unreachable: true,
+            ..Default::default()
+        };
+        let mut messages = Messages::default();
+        stub.verify_world_row_count(&mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("Could not count the rows of the world table"));
+        assert!(!messages.warnings[0].message.contains("0 rows"));
+    }
+
+    #[test]
+    fn it_should_classify_sql_column_types() {
+        assert_eq!(ColumnType::from_sql("integer"), ColumnType::Integer);
+        assert_eq!(ColumnType::from_sql("INT"), ColumnType::Integer);
+        assert_eq!(ColumnType::from_sql("character varying"), ColumnType::Text);
+        assert_eq!(ColumnType::from_sql("varchar"), ColumnType::Text);
+        assert_eq!(ColumnType::from_sql("nvarchar"), ColumnType::Text);
+        assert_eq!(
+            ColumnType::from_sql("double precision"),
+            ColumnType::Other("double precision".to_string())
+        );
+    }
+
+    #[test]
+    fn it_should_warn_about_schema_mismatches() {
+        let mut messages = Messages::default();
+        let world: HashMap<String, ColumnType> = vec![
+            ("id".to_string(), ColumnType::Integer),
+            ("randomnumber".to_string(), ColumnType::Integer),
+        ]
+        .into_iter()
+        .collect();
+        report_schema("world", &world, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let fortune: HashMap<String, ColumnType> = vec![("id".to_string(), ColumnType::Text)]
+            .into_iter()
+            .collect();
+        report_schema("fortune", &fortune, &mut messages);
+        assert_eq!(messages.warnings.len(), 2);
+        assert_eq!(
+            messages.warnings[0].message,
+            "The fortune.id column is text; expected an integer."
+        );
+        assert_eq!(
+            messages.warnings[1].message,
+            "The fortune table has no message column."
+        );
+    }
 }
@@ -1,42 +1,87 @@
-use crate::database::DatabaseInterface;
+use crate::config;
+use crate::database::{ColumnType, DatabaseInterface};
+use crate::error::VerifierError::{
+    DatabaseError, DatabaseUnreachable, InsufficientDatabasePrivileges,
+};
+use crate::error::VerifierResult;
 use crate::verification::Messages;
 use mongodb::bson::Bson;
 use mongodb::bson::Document;
+use mongodb::error::ErrorKind;
 use mongodb::sync::Client;
 use std::collections::HashMap;
-use std::thread::sleep;
 use std::time::Duration;
 
+/// The MongoDB error code for `Unauthorized`.
+const UNAUTHORIZED_CODE: i32 = 13;
+
 #[derive(Debug)]
 pub struct Mongodb {}
 impl Mongodb {
+    fn connection_url(host: &str) -> String {
+        format!("mongodb://{}", host)
+    }
+
     fn get_client(&self) -> mongodb::error::Result<Client> {
-        Client::with_uri_str("mongodb://tfb-database")
+        Client::with_uri_str(&config::database_urls("MONGODB_URL", Mongodb::connection_url).read)
+    }
+
+    /// Gets a client as `get_client` does, which gives up on connecting and
+    /// on selecting a server after `timeout`.
+    fn get_client_within(&self, timeout: Duration) -> mongodb::error::Result<Client> {
+        let url = config::database_urls("MONGODB_URL", Mongodb::connection_url).read;
+        Client::with_uri_str(&with_timeout(&url, timeout))
+    }
+
+    /// Gets a client connected to the `DatabaseUrls::write` url, e.g. the
+    /// `DB_WRITE_HOST`.
+    fn get_write_client(&self) -> mongodb::error::Result<Client> {
+        Client::with_uri_str(&config::database_urls("MONGODB_URL", Mongodb::connection_url).write)
+    }
+
+    /// Runs `serverStatus` and returns its `opcounters` document, if any.
+    fn get_op_counters(&self) -> VerifierResult<Option<Document>> {
+        let client = self
+            .get_client()
+            .map_err(|e| DatabaseUnreachable(e.to_string()))?;
+        let database = client.database("hello_world");
+        let mut command = Document::new();
+        command.insert("serverStatus", 1);
+        match database.run_command(command, None) {
+            Ok(bson_doc) => Ok(bson_doc.get_document("opcounters").ok().cloned()),
+            Err(e) => match e.kind.as_ref() {
+                ErrorKind::CommandError(command_error)
+                    if command_error.code == UNAUTHORIZED_CODE =>
+                {
+                    Err(InsufficientDatabasePrivileges(
+                        command_error.message.clone(),
+                    ))
+                }
+                ErrorKind::CommandError(command_error) => {
+                    Err(DatabaseError(command_error.message.clone()))
+                }
+                // Anything else is failing to reach the server at all.
+                _ => Err(DatabaseUnreachable(e.to_string())),
+            },
+        }
     }
 }
 impl DatabaseInterface for Mongodb {
-    fn wait_for_database_to_be_available(&self) {
-        let mut messages = Messages::default();
-        let max = 60;
-        let mut slept = 0;
-        while slept < max {
-            if self.get_client().is_ok() {
-                return;
+    fn ping(&self, timeout: Duration) -> bool {
+        match self.get_client_within(timeout) {
+            Ok(client) => {
+                let mut command = Document::new();
+                command.insert("serverStatus", 1);
+                client
+                    .database("hello_world")
+                    .run_command(command, None)
+                    .is_ok()
             }
-
-            sleep(Duration::from_secs(1));
-            slept += 1;
+            Err(_) => false,
         }
-        messages.error(
-            format!(
-                "Database connection could not be established after {} seconds.",
-                max
-            ),
-            "Database unavailable",
-        );
     }
 
-    fn get_all_from_world_table(&self) -> HashMap<i32, i32> {
+    fn get_all_from_world_table(&self, _messages: &mut Messages) -> HashMap<i32, i32> {
         let mut to_ret: HashMap<i32, i32> = HashMap::default();
         if let Ok(client) = self.get_client() {
             let database = client.database("hello_world");
@@ -58,8 +103,39 @@ impl DatabaseInterface for Mongodb {
         to_ret
     }
 
-    fn insert_one_thousand_fortunes(&self) {
-        if let Ok(client) = self.get_client() {
+    fn count_world_rows(&self) -> VerifierResult<i64> {
+        Ok(self
+            .get_client()?
+            .database("hello_world")
+            .collection("world")
+            .count_documents(None, None)?)
+    }
+
+    /// MongoDB has no schema, so the types are those of the fields of the
+    /// first document in the collection.
+    fn get_column_types(&self, table_name: &str) -> VerifierResult<HashMap<String, ColumnType>> {
+        let document = self
+            .get_client()?
+            .database("hello_world")
+            .collection(table_name)
+            .find_one(None, None)?;
+
+        Ok(document
+            .iter()
+            .flat_map(|document| document.iter())
+            .map(|(field, value)| {
+                let column_type = match value {
+                    Bson::Int32(_) | Bson::Int64(_) => ColumnType::Integer,
+                    Bson::String(_) => ColumnType::Text,
+                    other => ColumnType::Other(format!("{:?}", other.element_type())),
+                };
+                (field.to_lowercase(), column_type)
+            })
+            .collect())
+    }
+
+    fn insert_one_thousand_fortunes(&self, _messages: &mut Messages) {
+        if let Ok(client) = self.get_write_client() {
             let database = client.database("hello_world");
             for i in 0..1_000 {
                 let mut document = Document::new();
@@ -73,71 +149,121 @@ impl DatabaseInterface for Mongodb {
         }
     }
 
-    fn get_count_of_all_queries_for_table(&self, _table_name: &str) -> u32 {
-        if let Ok(client) = self.get_client() {
-            let database = client.database("hello_world");
-            let mut command = Document::new();
-            command.insert("serverStatus", 1);
-            if let Ok(bson_doc) = database.run_command(command, None) {
-                if let Ok(opcounters) = bson_doc.get_document("opcounters") {
-                    let mut sum = 0;
-                    if let Ok(update) = opcounters.get_i64("update") {
-                        sum += update as u32;
-                    }
-                    if let Ok(query) = opcounters.get_i64("query") {
-                        sum += query as u32;
-                    }
-                    return sum;
-                }
+    fn reset_fortune_table(&self, _messages: &mut Messages) {
+        if let Ok(client) = self.get_write_client() {
+            let mut filter = Document::new();
+            let mut at_least = Document::new();
+            at_least.insert("$gte", 13);
+            filter.insert("id", at_least);
+            // As with MySQL, a failure here is left to surface as a failed
+            // verification of the fortune test.
+            let _ = client
+                .database("hello_world")
+                .collection("fortune")
+                .delete_many(filter, None);
+        }
+    }
+
+    fn get_count_of_all_queries_for_table(&self, _table_name: &str) -> VerifierResult<u32> {
+        if let Some(op_counters) = self.get_op_counters()? {
+            let mut sum = 0;
+            if let Ok(update) = op_counters.get_i64("update") {
+                sum += update as u32;
+            }
+            if let Ok(query) = op_counters.get_i64("query") {
+                sum += query as u32;
             }
+            return Ok(sum);
         }
 
-        0
+        Ok(0)
     }
 
     fn get_count_of_rows_selected_for_table(
         &self,
         _table_name: &str,
         expected_rows_per_query: u32,
-    ) -> u32 {
-        if let Ok(client) = self.get_client() {
-            let database = client.database("hello_world");
-            let mut command = Document::new();
-            command.insert("serverStatus", 1);
-            if let Ok(bson_doc) = database.run_command(command, None) {
-                if let Ok(op_counters) = bson_doc.get_document("opcounters") {
-                    let mut sum = 0;
-                    if let Ok(query) = op_counters.get_i64("query") {
-                        sum += query as u32;
-                    }
-                    return sum * expected_rows_per_query;
-                }
+    ) -> VerifierResult<u32> {
+        if let Some(op_counters) = self.get_op_counters()? {
+            let mut sum = 0;
+            if let Ok(query) = op_counters.get_i64("query") {
+                sum += query as u32;
             }
+            return Ok(sum * expected_rows_per_query);
         }
 
-        0
+        Ok(0)
     }
 
     fn get_count_of_rows_updated_for_table(
         &self,
         _table_name: &str,
         expected_rows_per_query: u32,
-    ) -> u32 {
-        if let Ok(client) = self.get_client() {
-            let database = client.database("hello_world");
-            let mut command = Document::new();
-            command.insert("serverStatus", 1);
-            if let Ok(bson_doc) = database.run_command(command, None) {
-                if let Ok(op_counters) = bson_doc.get_document("opcounters") {
-                    let mut sum = 0;
-                    if let Ok(update) = op_counters.get_i64("update") {
-                        sum += update as u32;
-                    }
-                    return sum * expected_rows_per_query;
-                }
+    ) -> VerifierResult<u32> {
+        if let Some(op_counters) = self.get_op_counters()? {
+            let mut sum = 0;
+            if let Ok(update) = op_counters.get_i64("update") {
+                sum += update as u32;
             }
+            return Ok(sum * expected_rows_per_query);
         }
 
-        0
+        Ok(0)
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Appends `timeout` to the options of the connection string `url` as both
+/// the connect and server selection timeouts; the driver only parses
+/// `ClientOptions` asynchronously, while options in the url are honoured by
+/// the sync `Client` as well.
+fn with_timeout(url: &str, timeout: Duration) -> String {
+    let separator = if url.contains('?') {
+        "&"
+    } else if url
+        .split_once("://")
+        .map_or(url, |(_, hosts)| hosts)
+        .contains('/')
+    {
+        "?"
+    } else {
+        "/?"
+    };
+    format!(
+        "{}{}connectTimeoutMS={}&serverSelectionTimeoutMS={}",
+        url,
+        separator,
+        timeout.as_millis(),
+        timeout.as_millis()
+    )
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::database::mongodb::with_timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn it_should_append_timeout_to_url_options() {
+        let timeout = Duration::from_millis(1500);
+        assert_eq!(
+            with_timeout("mongodb://tfb-database", timeout),
+            "mongodb://tfb-database/?connectTimeoutMS=1500&serverSelectionTimeoutMS=1500"
+        );
+        assert_eq!(
+            with_timeout("mongodb://tfb-database/hello_world", timeout),
+            "mongodb://tfb-database/hello_world?connectTimeoutMS=1500&serverSelectionTimeoutMS=1500"
+        );
+        assert_eq!(
+            with_timeout("mongodb://tfb-database/?tls=true", timeout),
+            "mongodb://tfb-database/?tls=true&connectTimeoutMS=1500&serverSelectionTimeoutMS=1500"
+        );
     }
 }
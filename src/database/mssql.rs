@@ -0,0 +1,282 @@
+use crate::config;
+use crate::config::DatabaseUrls;
+use crate::database::{connected, ColumnType, DatabaseInterface};
+use crate::error::VerifierError::{
+    DatabaseError, DatabaseUnreachable, InsufficientDatabasePrivileges,
+};
+use crate::error::VerifierResult;
+use crate::verification::Messages;
+use std::collections::HashMap;
+use std::time::Duration;
+use tiberius::{Client, Config, EncryptionLevel, Row};
+use tokio::net::TcpStream;
+use tokio::runtime::{Builder, Runtime};
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+
+/// SQL Server error codes which indicate that the connected user is not
+/// allowed to read the statistics used for counting; e.g. when it lacks
+/// `VIEW SERVER STATE`.
+const ACCESS_DENIED_CODES: [u32; 2] = [297, 300];
+
+#[derive(Debug)]
+pub struct Mssql {}
+impl Mssql {
+    /// Gets the ADO.NET connection string for `host`, which tiberius takes in
+    /// place of a url.
+    fn connection_url(host: &str) -> String {
+        format!(
+            "server=tcp:{};database=hello_world;user=benchmarkdbuser;password=benchmarkdbpass;TrustServerCertificate=true",
+            host
+        )
+    }
+
+    fn urls() -> DatabaseUrls {
+        config::database_urls("MSSQL_URL", Mssql::connection_url)
+    }
+
+    fn get_client(&self, messages: &mut Messages) -> Option<Connection> {
+        connected(self.connect(&Mssql::urls().read), messages)
+    }
+
+    /// Gets a client connected to the `DatabaseUrls::write` url, e.g. the
+    /// `DB_WRITE_HOST`.
+    fn get_write_client(&self, messages: &mut Messages) -> Option<Connection> {
+        connected(self.connect(&Mssql::urls().write), messages)
+    }
+
+    /// Connects to `url`, over TLS when `config::database_tls` is set.
+    fn connect(&self, url: &str) -> VerifierResult<Connection> {
+        self.connect_within(url, None)
+    }
+
+    /// Connects to `url` as `connect` does, giving up on connecting after
+    /// `timeout`, if any.
+    fn connect_within(&self, url: &str, timeout: Option<Duration>) -> VerifierResult<Connection> {
+        let mut config = Config::from_ado_string(url)?;
+        config.encryption(encryption_level(config::database_tls()));
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let connecting = async {
+            let tcp = TcpStream::connect(config.get_addr()).await?;
+            tcp.set_nodelay(true)?;
+            Client::connect(config, tcp.compat_write()).await
+        };
+        let client = match timeout {
+            // The timer can only be created within the runtime.
+            Some(timeout) => runtime
+                .block_on(async { tokio::time::timeout(timeout, connecting).await })
+                .map_err(|_| {
+                    DatabaseError(format!("connection timed out after {:?}", timeout))
+                })??,
+            None => runtime.block_on(connecting)?,
+        };
+
+        Ok(Connection { runtime, client })
+    }
+
+    /// Runs the counting `query`; zero only when the database counted zero
+    /// (e.g. a `SUM` over no statements), never when it could not be asked.
+    fn run_counting_query(&self, query: &str) -> VerifierResult<u32> {
+        let mut client = self
+            .connect(&Mssql::urls().read)
+            .map_err(|e| DatabaseUnreachable(e.to_string()))?;
+        match client.query(query) {
+            Ok(rows) => {
+                let sum = rows.first().and_then(|row| row.get::<i64, _>(0));
+                Ok(sum.unwrap_or(0) as u32)
+            }
+            Err(e)
+                if e.code()
+                    .is_some_and(|code| ACCESS_DENIED_CODES.contains(&code)) =>
+            {
+                Err(InsufficientDatabasePrivileges(e.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+impl DatabaseInterface for Mssql {
+    fn ping(&self, timeout: Duration) -> bool {
+        match self.connect_within(&Mssql::urls().read, Some(timeout)) {
+            Ok(mut client) => client.query("SELECT 1").is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn get_all_from_world_table(&self, messages: &mut Messages) -> HashMap<i32, i32> {
+        let mut to_ret = HashMap::new();
+        if let Some(mut client) = self.get_client(messages) {
+            if let Ok(rows) = client.query("SELECT id, randomNumber FROM world") {
+                for row in rows {
+                    if let (Some(id), Some(random_number)) =
+                        (row.get::<i32, _>(0), row.get::<i32, _>(1))
+                    {
+                        to_ret.insert(id, random_number);
+                    }
+                }
+            }
+        }
+
+        to_ret
+    }
+
+    fn count_world_rows(&self) -> VerifierResult<i64> {
+        let mut client = self.connect(&Mssql::urls().read)?;
+        let rows = client.query("SELECT COUNT_BIG(*) FROM world")?;
+
+        Ok(rows
+            .first()
+            .and_then(|row| row.get::<i64, _>(0))
+            .unwrap_or(0))
+    }
+
+    fn get_column_types(&self, table_name: &str) -> VerifierResult<HashMap<String, ColumnType>> {
+        let mut client = self.connect(&Mssql::urls().read)?;
+        // Table names are only ever the verifier's own constants.
+        let rows = client.query(&format!(
+            "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = '{}'",
+            table_name
+        ))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| match (row.get::<&str, _>(0), row.get::<&str, _>(1)) {
+                (Some(column_name), Some(data_type)) => {
+                    Some((column_name.to_lowercase(), ColumnType::from_sql(data_type)))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn insert_one_thousand_fortunes(&self, messages: &mut Messages) {
+        if let Some(mut client) = self.get_write_client(messages) {
+            let mut insert = String::new();
+            for i in 0..1_000 {
+                insert.push_str(&format!(
+                    "INSERT INTO fortune(id,message) VALUES ({},N'フレームワークのベンチマーク');",
+                    i + 13
+                ));
+            }
+            // As with MySQL, a failure here is left to surface as a failed
+            // verification of the fortune test.
+            let _ = client.execute(&insert);
+        }
+    }
+
+    fn reset_fortune_table(&self, messages: &mut Messages) {
+        if let Some(mut client) = self.get_write_client(messages) {
+            let _ = client.execute("DELETE FROM fortune WHERE id >= 13;");
+        }
+    }
+
+    fn get_count_of_all_queries_for_table(&self, table_name: &str) -> VerifierResult<u32> {
+        self.run_counting_query(&counting_query("execution_count", table_name, None))
+    }
+
+    fn get_count_of_rows_selected_for_table(
+        &self,
+        table_name: &str,
+        _expected_rows_per_query: u32,
+    ) -> VerifierResult<u32> {
+        self.run_counting_query(&counting_query("total_rows", table_name, Some("select")))
+    }
+
+    fn get_count_of_rows_updated_for_table(
+        &self,
+        table_name: &str,
+        _expected_rows_per_query: u32,
+    ) -> VerifierResult<u32> {
+        self.run_counting_query(&counting_query("total_rows", table_name, Some("update")))
+    }
+}
+
+/// A client connected to SQL Server, with the runtime which drives it; the
+/// tiberius client is asynchronous, whereas the verifier is not.
+struct Connection {
+    runtime: Runtime,
+    client: Client<Compat<TcpStream>>,
+}
+impl Connection {
+    /// Runs `query` and returns the rows of its first result.
+    fn query(&mut self, query: &str) -> tiberius::Result<Vec<Row>> {
+        let client = &mut self.client;
+        self.runtime
+            .block_on(async { client.simple_query(query).await?.into_first_result().await })
+    }
+
+    /// Runs the `statements`, discarding any rows they return.
+    fn execute(&mut self, statements: &str) -> tiberius::Result<()> {
+        let client = &mut self.client;
+        self.runtime
+            .block_on(async { client.execute(statements, &[]).await })
+            .map(|_| ())
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Gets the `EncryptionLevel` with which to connect; everything is encrypted
+/// when `tls` is set, and otherwise only the login, as SQL Server does by
+/// default.
+fn encryption_level(tls: bool) -> EncryptionLevel {
+    if tls {
+        EncryptionLevel::Required
+    } else {
+        EncryptionLevel::Off
+    }
+}
+
+/// Builds the query summing `column` (`execution_count` or `total_rows`, as
+/// named by `sys.dm_exec_query_stats`) over the cached statements which
+/// reference `table_name` and, if given, match `statement`.
+///
+/// Statements which reference the statistics are excluded: they are the
+/// verifier's own counting queries, which name `table_name` themselves and
+/// would otherwise inflate the counts taken after them.
+///
+/// Note: the statistics are kept per cached plan, so a statement only counts
+/// once it has finished, and not at all once its plan is evicted.
+fn counting_query(column: &str, table_name: &str, statement: Option<&str>) -> String {
+    let mut query = format!(
+        "SELECT SUM(stats.{}) FROM sys.dm_exec_query_stats AS stats CROSS APPLY sys.dm_exec_sql_text(stats.sql_handle) AS text WHERE text.text LIKE '%{}%' AND text.text NOT LIKE '%dm_exec_query_stats%'",
+        column, table_name
+    );
+    if let Some(statement) = statement {
+        query.push_str(&format!(" AND text.text LIKE '%{}%'", statement));
+    }
+    query
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::database::mssql::{counting_query, encryption_level, Mssql};
+    use tiberius::{Config, EncryptionLevel};
+
+    #[test]
+    fn it_should_parse_the_connection_url() {
+        let config = Config::from_ado_string(&Mssql::connection_url("tfb-database,1433")).unwrap();
+        assert_eq!(config.get_addr(), "tfb-database:1433");
+    }
+
+    #[test]
+    fn it_should_encrypt_everything_when_configured() {
+        assert_eq!(encryption_level(true), EncryptionLevel::Required);
+        assert_eq!(encryption_level(false), EncryptionLevel::Off);
+    }
+
+    #[test]
+    fn it_should_count_from_query_stats() {
+        assert_eq!(
+            counting_query("execution_count", "fortune", None),
+            "SELECT SUM(stats.execution_count) FROM sys.dm_exec_query_stats AS stats CROSS APPLY sys.dm_exec_sql_text(stats.sql_handle) AS text WHERE text.text LIKE '%fortune%' AND text.text NOT LIKE '%dm_exec_query_stats%'"
+        );
+        assert!(counting_query("total_rows", "world", Some("update"))
+            .ends_with(" AND text.text LIKE '%update%'"));
+    }
+}
@@ -0,0 +1,115 @@
+//! A `DatabaseInterface` for tests which answers from canned values rather
+//! than connecting to a running database.
+
+use crate::database::{ColumnType, DatabaseInterface, EXPECTED_COLUMNS};
+use crate::error::VerifierError::{DatabaseUnreachable, InsufficientDatabasePrivileges};
+use crate::error::VerifierResult;
+use crate::verification::Messages;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Stub {
+    /// Returned from `get_all_from_world_table`.
+    pub world: HashMap<i32, i32>,
+    /// When set, every counting query fails as though the database user
+    /// were not allowed to read statistics.
+    pub deny_statistics: bool,
+    /// When set, `ping` and every counting query fail as though the database
+    /// could not be reached.
+    pub unreachable: bool,
+    /// When set, `wait_for_database_to_be_available` fails as though the
+    /// database never accepted a connection.
+    pub unavailable: bool,
+    /// The times any method of the `DatabaseInterface` was called; shared, so
+    /// that it can be read once the `Stub` is owned by an `Executor`.
+    pub calls: Arc<AtomicU32>,
+}
+impl Stub {
+    fn called(&self) {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn count(&self) -> VerifierResult<u32> {
+        self.called();
+        if self.unreachable {
+            Err(DatabaseUnreachable("connection refused".to_string()))
+        } else if self.deny_statistics {
+            Err(InsufficientDatabasePrivileges(
+                "permission denied for view pg_stat_statements".to_string(),
+            ))
+        } else {
+            Ok(0)
+        }
+    }
+}
+impl DatabaseInterface for Stub {
+    fn wait_for_database_to_be_available(&self, messages: &mut Messages) -> bool {
+        self.called();
+        if self.unavailable {
+            messages.error(
+                "Database connection could not be established after 0 seconds.",
+                "Database unavailable",
+            );
+        }
+        !self.unavailable
+    }
+
+    fn ping(&self, _timeout: Duration) -> bool {
+        self.called();
+        !self.unreachable
+    }
+
+    fn get_all_from_world_table(&self, _messages: &mut Messages) -> HashMap<i32, i32> {
+        self.called();
+        self.world.clone()
+    }
+
+    /// The rows of `world`, unless `unreachable`.
+    fn count_world_rows(&self) -> VerifierResult<i64> {
+        self.called();
+        if self.unreachable {
+            return Err(DatabaseUnreachable("connection refused".to_string()));
+        }
+        Ok(self.world.len() as i64)
+    }
+
+    /// Every table has the expected columns, unless `unreachable`.
+    fn get_column_types(&self, table_name: &str) -> VerifierResult<HashMap<String, ColumnType>> {
+        self.called();
+        if self.unreachable {
+            return Err(DatabaseUnreachable("connection refused".to_string()));
+        }
+        Ok(EXPECTED_COLUMNS
+            .iter()
+            .filter(|(table, _, _)| *table == table_name)
+            .map(|(_, column, column_type)| (column.to_string(), column_type.clone()))
+            .collect())
+    }
+
+    fn insert_one_thousand_fortunes(&self, _messages: &mut Messages) {
+        self.called();
+    }
+
+    fn get_count_of_all_queries_for_table(&self, _table_name: &str) -> VerifierResult<u32> {
+        self.count()
+    }
+
+    fn get_count_of_rows_selected_for_table(
+        &self,
+        _table_name: &str,
+        _rows_per_query: u32,
+    ) -> VerifierResult<u32> {
+        self.count()
+    }
+
+    fn get_count_of_rows_updated_for_table(
+        &self,
+        _table_name: &str,
+        _expected_rows_per_query: u32,
+    ) -> VerifierResult<u32> {
+        self.count()
+    }
+}
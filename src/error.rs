@@ -18,18 +18,84 @@ pub enum VerifierError {
     #[error("Environment variable error occurred")]
     EnvVarError(#[from] env::VarError),
 
+    #[error("Required environment variable not set: {0}")]
+    MissingEnvironmentVariable(String),
+
     #[error("Parse int error occurred")]
     ParseIntError(#[from] num::ParseIntError),
 
     #[error("Strum parse error occurred")]
     StrumParseError(#[from] strum::ParseError),
 
+    #[error("Invalid concurrency level in {0}: {1:?}")]
+    InvalidConcurrencyLevel(String, String),
+
     #[error("Invalid database type error: {0}")]
     InvalidDatabaseType(String),
 
-    #[error("Non-200 response from {0}: {1}")]
-    Non200Response(String, u32),
+    #[error("Insufficient database privileges to read statistics: {0}")]
+    InsufficientDatabasePrivileges(String),
+
+    #[error("Could not reach database while counting queries: {0}")]
+    DatabaseUnreachable(String),
+
+    #[error("{0}")]
+    DatabaseError(String),
+
+    /// The url, the response code, and a snippet of the response body, which
+    /// frameworks often use to explain a server error.
+    #[error("Non-200 response from {0}: {1}{}", with_body(.2))]
+    Non200Response(String, u32, String),
+
+    /// The url and the `Location` to which it redirected.
+    #[error("Unexpected redirect from {0} to {1}")]
+    UnexpectedRedirect(String, String),
 
     #[error("Error requesting {0}: {1}")]
     RequestError(String, String),
+
+    #[error("Response from {0} exceeded {1} bytes")]
+    ResponseTooLarge(String, usize),
+}
+
+/// Formats the response body `snippet` for appending to an error message.
+fn with_body(snippet: &str) -> String {
+    if snippet.is_empty() {
+        String::new()
+    } else {
+        format!("; body: {}", snippet)
+    }
+}
+
+// The database drivers' errors are carried as their messages; the drivers
+// have no common error type and callers only ever report them.
+
+impl From<mysql::Error> for VerifierError {
+    fn from(error: mysql::Error) -> Self {
+        VerifierError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<mysql::UrlError> for VerifierError {
+    fn from(error: mysql::UrlError) -> Self {
+        VerifierError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<postgres::Error> for VerifierError {
+    fn from(error: postgres::Error) -> Self {
+        VerifierError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<tiberius::error::Error> for VerifierError {
+    fn from(error: tiberius::error::Error) -> Self {
+        VerifierError::DatabaseError(error.to_string())
+    }
+}
+
+impl From<mongodb::error::Error> for VerifierError {
+    fn from(error: mongodb::error::Error) -> Self {
+        VerifierError::DatabaseError(error.to_string())
+    }
 }
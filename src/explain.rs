@@ -0,0 +1,57 @@
+//! The `explain` module is the mechanism by which `TFBVerifier` reports the
+//! counts it expects to observe in the database while verifying a `TestType`,
+//! without verifying anything. These are serialized for consumption by the
+//! `TFBToolset`, which may cross-check them, and by maintainers auditing how
+//! they are derived.
+
+use serde::Serialize;
+
+/// The counts which a database-backed `Executor` expects after sending its
+/// requests `repetitions` times at the given `concurrency`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ExpectedCounts {
+    pub concurrency: u32,
+    pub repetitions: u32,
+    pub expected_queries: u32,
+    pub expected_rows: u32,
+    /// Only the `update` test type expects rows to be updated.
+    pub expected_updates: Option<u32>,
+}
+
+/// Prints and returns the serialized `ExpectedCounts`; `null` for a test
+/// type which does not count anything in the database.
+pub fn send_expected_counts(counts: Option<ExpectedCounts>) -> String {
+    let to_ret = serde_json::to_string(&counts).unwrap();
+    println!("{}", to_ret);
+    to_ret
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::explain::{send_expected_counts, ExpectedCounts};
+    use serde_json::Value;
+
+    #[test]
+    fn it_can_serialize_expected_counts() {
+        let serialized = send_expected_counts(Some(ExpectedCounts {
+            concurrency: 512,
+            repetitions: 2,
+            expected_queries: 1024,
+            expected_rows: 12288,
+            expected_updates: None,
+        }));
+        let json = serde_json::from_str::<Value>(&serialized).unwrap();
+        assert_eq!(json["expected_queries"], 1024);
+        assert_eq!(json["expected_rows"], 12288);
+        assert!(json["expected_updates"].is_null());
+    }
+
+    #[test]
+    fn it_can_serialize_no_expected_counts() {
+        assert_eq!(send_expected_counts(None), "null");
+    }
+}
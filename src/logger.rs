@@ -1,16 +1,39 @@
+use crate::config;
+use chrono::{DateTime, SecondsFormat, Utc};
 use colored::{ColoredString, Colorize};
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use strum_macros::EnumString;
+
+/// How much is logged (`LOG_LEVEL`); each level includes those before it.
+#[derive(EnumString, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[strum(serialize_all = "lowercase")]
+pub enum LogLevel {
+    /// Nothing but the messages serialized for the toolset.
+    Quiet,
+    /// The progress of verification; the default.
+    Info,
+    /// Additionally, the raw requests and responses.
+    Debug,
+}
 
 pub struct LogOptions {
     pub border: Option<char>,
     pub border_bottom: Option<char>,
-    pub quiet: bool,
+    /// The level at which this text is logged.
+    pub level: LogLevel,
 }
 
-/// Logs the given text to stdout (if quiet is False) and
-/// to an optional log file. By default, we strip out newlines in order to
-/// print our lines correctly, but you can override this functionality if you
-/// want to print multi-line output.
+/// Logs the given text to stdout (if its level is enabled by
+/// `config::log_level`) and to the optional `config::log_file`. By default, we strip out
+/// newlines in order to print our lines correctly, but you can override this
+/// functionality if you want to print multi-line output.
 pub fn log(text: ColoredString, options: LogOptions) {
+    if !is_enabled(options.level, config::log_level()) {
+        return;
+    }
     let mut border_string = ColoredString::from("");
     if let Some(border) = options.border {
         let mut buffer = String::new();
@@ -28,6 +51,7 @@ pub fn log(text: ColoredString, options: LogOptions) {
     }
 
     println!("{}{}", text, ColoredString::from("").clear());
+    log_to_file(&text);
 
     if let Some(_border_bottom) = options.border_bottom {
         // This is a hold-over from legacy - if a use for this block is not
@@ -36,3 +60,86 @@ pub fn log(text: ColoredString, options: LogOptions) {
         println!("{}{}", border_string, ColoredString::from("").clear());
     }
 }
+
+/// Appends `line` to the `config::log_file`, if any, with a timestamp.
+///
+/// Note: a log file which cannot be written to is ignored; it is only a copy
+/// and must not fail verification.
+pub fn log_to_file(line: &str) {
+    if let Some(path) = config::log_file() {
+        let _ = append_line(Path::new(&path), &timestamped(Utc::now(), line));
+    }
+}
+
+//
+// PRIVATES
+//
+
+fn timestamped(at: DateTime<Utc>, line: &str) -> String {
+    format!(
+        "[{}] {}",
+        at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        line
+    )
+}
+
+fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Whether text logged at `level` is shown when logging at `configured`.
+fn is_enabled(level: LogLevel, configured: LogLevel) -> bool {
+    level != LogLevel::Quiet && level <= configured
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::logger::{append_line, is_enabled, timestamped, LogLevel};
+    use chrono::{TimeZone, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn it_should_log_info_by_default() {
+        assert!(is_enabled(LogLevel::Info, LogLevel::Info));
+        assert!(!is_enabled(LogLevel::Debug, LogLevel::Info));
+    }
+
+    #[test]
+    fn it_should_log_everything_at_debug() {
+        assert!(is_enabled(LogLevel::Info, LogLevel::Debug));
+        assert!(is_enabled(LogLevel::Debug, LogLevel::Debug));
+    }
+
+    #[test]
+    fn it_should_timestamp_log_file_lines() {
+        let at = Utc.ymd(2021, 3, 4).and_hms_milli(5, 6, 7, 89);
+        assert_eq!(
+            timestamped(at, "VERIFYING json"),
+            "[2021-03-04T05:06:07.089Z] VERIFYING json"
+        );
+    }
+
+    #[test]
+    fn it_should_append_to_log_file() {
+        let path = env::temp_dir().join(format!("tfb-verifier-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append_line(&path, "first").unwrap();
+        append_line(&path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_should_log_nothing_when_quiet() {
+        for level in [LogLevel::Quiet, LogLevel::Info, LogLevel::Debug] {
+            assert!(!is_enabled(level, LogLevel::Quiet));
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod benchmark;
+mod config;
 mod database;
 mod error;
+mod explain;
 mod logger;
 mod mode;
 mod request;
@@ -11,72 +13,495 @@ extern crate html5ever;
 extern crate strum;
 extern crate threadpool;
 
-use crate::benchmark::send_benchmark_commands;
-use crate::error::VerifierResult;
-use crate::logger::{log, LogOptions};
+use crate::benchmark::{print_benchmark_commands, send_benchmark_commands};
+use crate::database::Database;
+use crate::error::VerifierError::{InvalidConcurrencyLevel, MissingEnvironmentVariable};
+use crate::error::{VerifierError, VerifierResult};
+use crate::explain::send_expected_counts;
+use crate::logger::{log, LogLevel, LogOptions};
 use crate::mode::Mode;
-use crate::test_type::TestType;
+use crate::request::request_totals;
+use crate::test_type::{Executor, TestType};
 use crate::verification::Messages;
 use colored::Colorize;
 use std::env;
+use std::net::Ipv6Addr;
+use std::process;
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Instant;
 
 fn main() -> VerifierResult<()> {
-    let mode_name = env::var("MODE")?;
-    let port = env::var("PORT")?.parse::<u32>()?;
-    let endpoint = env::var("ENDPOINT")?;
-    let test_type_name = env::var("TEST_TYPE")?;
-    let concurrency_levels = env::var("CONCURRENCY_LEVELS")?;
-    let pipeline_concurrency_levels = env::var("PIPELINE_CONCURRENCY_LEVELS")?;
-    let database = match env::var("DATABASE") {
-        Ok(database) => Some(database),
-        _ => None,
-    };
-
-    let test_type = TestType::get(&test_type_name)?;
-    let url = format!("http://{}:{}{}", "tfb-server", port, endpoint);
-
-    let executor = test_type.get_executor(
-        &database,
-        concurrency_levels
-            .split(',')
-            .map(|item| u32::from_str(item).unwrap())
-            .collect(),
-        pipeline_concurrency_levels
-            .split(',')
-            .map(|item| u32::from_str(item).unwrap())
-            .collect(),
-    )?;
+    let mode_name = required_var("MODE")?;
 
     match Mode::get(&mode_name)? {
         Mode::Database => {
-            if database.as_ref().is_some() {
-                // Block until the database is accepting requests
-                executor.wait_for_database_to_be_available();
-            }
+            // Only the database is needed; no test type or endpoint.
+            let messages = Database::health_check(&required_var("DATABASE")?);
+            messages.output_verification_results();
+            process::exit(exit_code(&messages, config::strict()));
         }
         Mode::Benchmark => {
-            let benchmark = executor.retrieve_benchmark_commands(&url)?;
+            // Only the first endpoint is benchmarked; the toolset expects a
+            // single set of commands.
+            let (test_type_name, urls) = get_test_urls()?;
+            let executor = get_executor(&test_type_name)?;
+            let benchmark = executor.retrieve_benchmark_commands(&urls[0])?;
             send_benchmark_commands(benchmark);
         }
+        Mode::Explain => {
+            // Reports the expected counts without requesting anything.
+            let executor = get_executor(&required_var("TEST_TYPE")?)?;
+            send_expected_counts(executor.expected_counts());
+        }
+        Mode::PrintExpected => {
+            // Prints the canonical bodies without requesting anything.
+            let test_type_name = required_var("TEST_TYPE")?;
+            let bodies = get_executor(&test_type_name)?.expected_bodies();
+            if bodies.is_empty() {
+                println!("No fixed response body is expected for {}", test_type_name);
+            }
+            for body in bodies {
+                println!("{}", body);
+            }
+        }
+        Mode::PrintCommands => {
+            // Prints the commands without requesting anything.
+            let (test_type_name, urls) = get_test_urls()?;
+            let executor = get_executor(&test_type_name)?;
+            for url in urls {
+                print_benchmark_commands(&executor.retrieve_benchmark_commands(&url)?);
+            }
+        }
         Mode::Verify => {
+            let (test_type_name, urls) = get_test_urls()?;
+            let deadline = config::verify_deadline();
+            let started = Instant::now();
+            let verified = verify_in_background(test_type_name, urls.clone());
+            let mut combined = Messages::default();
+            for _ in &urls {
+                // Whatever finished before the deadline is still reported.
+                let received = match deadline {
+                    Some(deadline) => {
+                        verified.recv_timeout(deadline.saturating_sub(started.elapsed()))
+                    }
+                    None => verified.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(messages) => combined.merge(messages?),
+                    Err(RecvTimeoutError::Timeout) => {
+                        combined.error(
+                            format!(
+                                "Verification exceeded deadline of {} seconds",
+                                deadline.unwrap_or_default().as_secs()
+                            ),
+                            "Deadline Exceeded",
+                        );
+                        break;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        combined.error(
+                            "Verification stopped before finishing",
+                            "Verification Failed",
+                        );
+                        break;
+                    }
+                }
+            }
+            if urls.len() > 1 {
+                println!(
+                    "   {} endpoints verified: {} errors, {} warnings",
+                    urls.len(),
+                    combined.errors.len(),
+                    combined.warnings.len()
+                );
+            }
+            let (issued, failed) = request_totals();
+            println!("   Total requests issued: {}, failures: {}", issued, failed);
+            process::exit(exit_code(&combined, config::strict()));
+        }
+        Mode::Unknown(mode) => {
+            let mut messages = Messages::default();
+            messages.error(format!("Invalid mode: {}", mode), "Invalid Mode");
+        }
+    };
+
+    Ok(())
+}
+
+//
+// PRIVATES
+//
+
+/// Gets the required environment variable `name`, failing with its name if it
+/// is not set; it may be empty, e.g. `ENDPOINT` for the root.
+fn required_var(name: &str) -> VerifierResult<String> {
+    match env::var(name) {
+        Err(env::VarError::NotPresent) => Err(MissingEnvironmentVariable(name.to_string())),
+        value => Ok(value?),
+    }
+}
+
+/// Gets the `TEST_TYPE` name and the urls of its comma-separated `ENDPOINT`s
+/// on `PORT`; an empty `PORT` is left out of the urls.
+fn get_test_urls() -> VerifierResult<(String, Vec<String>)> {
+    let port = match required_var("PORT")?.trim() {
+        "" => None,
+        port => Some(port.parse::<u32>()?),
+    };
+    let endpoints = required_var("ENDPOINT")?;
+    let test_type_name = required_var("TEST_TYPE")?;
+    let authority = url_authority(&config::server_scheme(), &config::server_host(), port);
+
+    Ok((test_type_name, endpoint_urls(&authority, &endpoints)))
+}
+
+/// Gets the url of each of the comma-separated `endpoints` under `authority`.
+/// There is always at least one, so that an empty `ENDPOINT` still verifies
+/// the root as it always has.
+fn endpoint_urls(authority: &str, endpoints: &str) -> Vec<String> {
+    let mut endpoints: Vec<&str> = endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .collect();
+    if endpoints.is_empty() {
+        endpoints.push("");
+    }
+
+    endpoints
+        .iter()
+        .map(|endpoint| format!("{}{}", authority, endpoint))
+        .collect()
+}
+
+/// Gets the url of `host` on `port` by `scheme`, e.g. `http://tfb-server:8080`.
+/// The port is left out when it is `None` or the scheme's default, since some
+/// servers match a `Host` of `host:80` literally.
+fn url_authority(scheme: &str, host: &str, port: Option<u32>) -> String {
+    match port {
+        Some(80) if scheme == "http" => format!("{}://{}", scheme, url_host(host)),
+        Some(443) if scheme == "https" => format!("{}://{}", scheme, url_host(host)),
+        Some(port) => format!("{}://{}:{}", scheme, url_host(host), port),
+        None => format!("{}://{}", scheme, url_host(host)),
+    }
+}
+
+/// Gets the process exit code for the verification which reported `messages`:
+/// non-zero if any endpoint had an error. Warnings do not fail verification
+/// unless `strict`.
+fn exit_code(messages: &Messages, strict: bool) -> i32 {
+    let failed = !messages.errors.is_empty() || (strict && !messages.warnings.is_empty());
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+/// Gets `host` as it is written in a url; IPv6 addresses are bracketed so
+/// that their colons are not taken for the port separator.
+fn url_host(host: &str) -> String {
+    match host.parse::<Ipv6Addr>() {
+        Ok(_) => format!("[{}]", host),
+        Err(_) => host.to_string(),
+    }
+}
+
+/// Verifies each of `urls` as `test_type_name` on a worker thread, sending
+/// the `Messages` of each as it finishes, so that the caller need not wait on
+/// a stalled verification. Stops at the first which cannot be verified.
+fn verify_in_background(
+    test_type_name: String,
+    urls: Vec<String>,
+) -> Receiver<VerifierResult<Messages>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let executor = match get_executor(&test_type_name) {
+            Ok(executor) => executor,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        for url in &urls {
+            let title = if urls.len() > 1 {
+                format!("VERIFYING {} ({})", executor.name(), url)
+            } else {
+                format!("VERIFYING {}", executor.name())
+            };
             log(
-                format!("VERIFYING {}", test_type_name).bright_white(),
+                title.bright_white(),
                 LogOptions {
                     border: Some('-'),
                     border_bottom: None,
-                    quiet: false,
+                    level: LogLevel::Info,
                 },
             );
 
-            let messages = executor.verify(&url)?;
-            messages.output_verification_results();
-        }
-        Mode::Unknown(mode) => {
-            let mut messages = Messages::default();
-            messages.error(format!("Invalid mode: {}", mode), "Invalid Mode");
+            let verified =
+                verify_url(executor.as_ref(), url, config::headers_only()).map(|mut messages| {
+                    messages.test_type(executor.name());
+                    messages.output_verification_results();
+                    messages
+                });
+            let failed = verified.is_err();
+            if sender.send(verified).is_err() || failed {
+                return;
+            }
         }
+    });
+
+    receiver
+}
+
+/// Verifies `url` with `executor` once it passes the smoke test; only its
+/// headers when `headers_only` is set.
+fn verify_url(executor: &dyn Executor, url: &str, headers_only: bool) -> VerifierResult<Messages> {
+    match executor.smoke_test(url) {
+        Ok(()) if headers_only => executor.verify_headers_only(url),
+        Ok(()) => executor.verify(url),
+        Err(e) => Ok(smoke_test_failure(url, e)),
+    }
+}
+
+/// Gets the `Messages` reporting that `url` failed its smoke test with `error`,
+/// in place of verifying it.
+fn smoke_test_failure(url: &str, error: VerifierError) -> Messages {
+    let reason = error.to_string();
+    let mut messages = Messages::new(url);
+    messages.error(
+        format!(
+            "Smoke test of {} failed; not verifying further: {}",
+            url, reason
+        ),
+        "Smoke Test Failed",
+    );
+
+    messages
+}
+
+/// Gets the `Executor` for `test_type_name`, configured from the environment.
+fn get_executor(test_type_name: &str) -> VerifierResult<Box<dyn Executor>> {
+    let concurrency_levels = required_var("CONCURRENCY_LEVELS")?;
+    let pipeline_concurrency_levels = required_var("PIPELINE_CONCURRENCY_LEVELS")?;
+    let database = match env::var("DATABASE") {
+        Ok(database) => Some(database),
+        _ => None,
     };
 
-    Ok(())
+    TestType::get(test_type_name)?.get_executor(
+        &database,
+        parse_concurrency_levels("CONCURRENCY_LEVELS", &concurrency_levels)?,
+        parse_concurrency_levels("PIPELINE_CONCURRENCY_LEVELS", &pipeline_concurrency_levels)?,
+        config::query_count_repetitions(),
+    )
+}
+
+/// Parses the comma-separated `levels` of the environment variable `name`,
+/// naming the first token which is not a concurrency level. The levels are
+/// sorted and de-duplicated, so that each is benchmarked once and in order.
+///
+/// Note: an empty `levels` is a single empty token, which does not parse, so
+/// there is always a highest level at which the executors benchmark.
+fn parse_concurrency_levels(name: &str, levels: &str) -> VerifierResult<Vec<u32>> {
+    let mut parsed = levels
+        .split(',')
+        .map(|item| {
+            u32::from_str(item.trim())
+                .map_err(|_| InvalidConcurrencyLevel(name.to_string(), item.to_string()))
+        })
+        .collect::<VerifierResult<Vec<u32>>>()?;
+    parsed.sort_unstable();
+    parsed.dedup();
+
+    Ok(parsed)
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::database::stub::Stub;
+    use crate::error::VerifierError::{InvalidConcurrencyLevel, Non200Response};
+    use crate::test_type::TestType;
+    use crate::verification::Messages;
+    use crate::{
+        endpoint_urls, exit_code, parse_concurrency_levels, required_var, smoke_test_failure,
+        url_authority, url_host, verify_url,
+    };
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+
+    #[test]
+    fn it_should_parse_concurrency_levels() {
+        let levels = parse_concurrency_levels("CONCURRENCY_LEVELS", "16,32, 64").unwrap();
+        assert_eq!(levels, vec![16, 32, 64]);
+    }
+
+    #[test]
+    fn it_should_sort_and_deduplicate_concurrency_levels() {
+        assert_eq!(
+            parse_concurrency_levels("CONCURRENCY_LEVELS", "16,16,8").unwrap(),
+            vec![8, 16]
+        );
+        assert_eq!(
+            parse_concurrency_levels("CONCURRENCY_LEVELS", "512, 16,64,16").unwrap(),
+            vec![16, 64, 512]
+        );
+    }
+
+    #[test]
+    fn it_should_reject_empty_concurrency_levels() {
+        match parse_concurrency_levels("PIPELINE_CONCURRENCY_LEVELS", "") {
+            Err(InvalidConcurrencyLevel(name, _)) => {
+                assert_eq!(name, "PIPELINE_CONCURRENCY_LEVELS")
+            }
+            _ => panic!("empty levels should not parse"),
+        }
+    }
+
+    #[test]
+    fn it_should_name_invalid_concurrency_level() {
+        for (levels, token) in [("16,32,", ""), ("", ""), ("16,lots", "lots")] {
+            match parse_concurrency_levels("CONCURRENCY_LEVELS", levels) {
+                Err(InvalidConcurrencyLevel(name, item)) => {
+                    assert_eq!(name, "CONCURRENCY_LEVELS");
+                    assert_eq!(item, token);
+                }
+                _ => panic!("{:?} should not parse", levels),
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_bracket_ipv6_hosts() {
+        assert_eq!(url_host("::1"), "[::1]");
+        assert_eq!(
+            format!("http://{}:{}{}", url_host("::1"), 8080, "/json"),
+            "http://[::1]:8080/json"
+        );
+        assert_eq!(url_host("[::1]"), "[::1]");
+        assert_eq!(url_host("127.0.0.1"), "127.0.0.1");
+        assert_eq!(url_host("tfb-server"), "tfb-server");
+    }
+
+    #[test]
+    fn it_should_name_a_missing_required_variable() {
+        assert_eq!(
+            required_var("TFB_VERIFIER_UNSET_VARIABLE")
+                .unwrap_err()
+                .to_string(),
+            "Required environment variable not set: TFB_VERIFIER_UNSET_VARIABLE"
+        );
+    }
+
+    #[test]
+    fn it_should_get_a_url_per_endpoint() {
+        assert_eq!(
+            endpoint_urls("http://tfb-server:8080", "/json, /jsonp"),
+            vec![
+                "http://tfb-server:8080/json",
+                "http://tfb-server:8080/jsonp"
+            ]
+        );
+        assert_eq!(
+            endpoint_urls("http://tfb-server:8080", ""),
+            vec!["http://tfb-server:8080"]
+        );
+    }
+
+    #[test]
+    fn it_should_leave_default_ports_out_of_urls() {
+        assert_eq!(
+            url_authority("http", "tfb-server", Some(80)),
+            "http://tfb-server"
+        );
+        assert_eq!(
+            url_authority("https", "tfb-server", Some(443)),
+            "https://tfb-server"
+        );
+        assert_eq!(
+            url_authority("http", "tfb-server", Some(8080)),
+            "http://tfb-server:8080"
+        );
+        assert_eq!(
+            url_authority("https", "tfb-server", Some(80)),
+            "https://tfb-server:80"
+        );
+        assert_eq!(url_authority("http", "::1", None), "http://[::1]");
+        assert_eq!(
+            url_authority("http", "::1", Some(8080)),
+            "http://[::1]:8080"
+        );
+    }
+
+    #[test]
+    fn it_should_exit_with_failure_only_on_errors() {
+        let mut messages = Messages::default();
+        assert_eq!(exit_code(&messages, false), 0);
+
+        messages.warning("Chunked response", "Chunked Response");
+        assert_eq!(exit_code(&messages, false), 0);
+
+        messages.error("Invalid JSON", "Invalid JSON");
+        assert_eq!(exit_code(&messages, false), 1);
+    }
+
+    #[test]
+    fn it_should_exit_with_failure_on_warnings_when_strict() {
+        let mut messages = Messages::default();
+        assert_eq!(exit_code(&messages, true), 0);
+
+        messages.warning("Chunked response", "Chunked Response");
+        assert_eq!(exit_code(&messages, true), 1);
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_report_a_failed_smoke_test_once() {
+        let url = "http://tfb-server:8080/json";
+        let messages = smoke_test_failure(
+            url,
+            Non200Response(url.to_string(), 404, "Not Found".to_string()),
+        );
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .ends_with("Non-200 response from http://tfb-server:8080/json: 404; body: Not Found"));
+    }
+
+    #[test]
+    fn it_should_verify_only_headers_without_the_database() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/db", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nServer: stub\r\nContent-Type: application/json\r\n\
+                      Content-Length: 2\r\nConnection: close\r\n\r\n{}",
+                );
+            }
+        });
+        let stub = Stub::default();
+        let calls = stub.calls.clone();
+        let executor =
+            TestType::SingleQuery.get_executor_for(Some(Box::new(stub)), vec![], vec![], 1);
+
+        let messages = verify_url(executor.as_ref(), &url, true).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        // The missing Date header shows the header checks ran.
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("Required response header missing: Date"));
+    }
 }
@@ -8,6 +8,11 @@ pub enum Mode {
     Database,
     Verify,
     Benchmark,
+    Explain,
+    #[strum(serialize = "print_expected")]
+    PrintExpected,
+    #[strum(serialize = "print_commands")]
+    PrintCommands,
     Unknown(String),
 }
 impl Mode {
@@ -1,55 +1,293 @@
-use crate::error::VerifierError::{CurlError, Non200Response, RequestError};
-use crate::error::VerifierResult;
-use crate::logger::{log, LogOptions};
+use crate::config;
+use crate::error::VerifierError::{
+    CurlError, Non200Response, RequestError, ResponseTooLarge, UnexpectedRedirect,
+};
+use crate::error::{VerifierError, VerifierResult};
+use crate::logger::{log, LogLevel, LogOptions};
 use crate::verification::Messages;
 use colored::Colorize;
-use curl::easy::{Easy, Easy2, Handler, WriteError};
+use curl::easy::{Easy2, Handler, List, WriteError};
 use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::ops::Deref;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use strum_macros::AsRefStr;
+
+/// The longest to wait on the connection while pipelining.
+const PIPELINE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The requests issued by this process, and how many of them failed to get
+/// any response; see `request_totals`.
+static REQUESTS_ISSUED: AtomicUsize = AtomicUsize::new(0);
+static REQUESTS_FAILED: AtomicUsize = AtomicUsize::new(0);
 
 pub enum ContentType {
     Json,
     Plaintext,
     Html,
 }
+impl ContentType {
+    /// The default `Accept` header sent by the benchmark (`wrk`) for test types
+    /// which respond with this `ContentType`. Verification requests send the
+    /// same, so that content negotiation cannot differ between the two.
+    ///
+    /// Note: `Html` (fortune) is benchmarked with the same header as `Json`.
+    pub fn accept(&self) -> &'static str {
+        match self {
+            ContentType::Json | ContentType::Html => "application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            ContentType::Plaintext => "text/plain,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+        }
+    }
+
+    /// The `Accept` header written into the benchmark commands; `accept`
+    /// unless overridden with `config::accept_header`.
+    ///
+    /// Note: the override is only benchmarked; verification still sends
+    /// `accept`.
+    pub fn benchmark_accept(&self) -> String {
+        config::accept_header().unwrap_or_else(|| self.accept().to_string())
+    }
+}
+
+/// An HTTP method with which the verifier may issue a request.
+#[derive(AsRefStr, Clone, Copy, Debug, PartialEq)]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    /// As `Get`, but the response has no body.
+    Head,
+}
 
-struct Collector(Vec<u8>);
+struct Collector {
+    body: Vec<u8>,
+    headers: Vec<String>,
+    /// The most bytes of `body` to collect; see `config::max_response_bytes`.
+    max_bytes: usize,
+    /// Whether the transfer was aborted for exceeding `max_bytes`.
+    exceeded: bool,
+}
+impl Default for Collector {
+    fn default() -> Self {
+        Collector {
+            body: Vec::new(),
+            headers: Vec::new(),
+            max_bytes: config::max_response_bytes(),
+            exceeded: false,
+        }
+    }
+}
 impl Handler for Collector {
+    /// Collects `data` into the `body`; taking none of it when that would
+    /// exceed `max_bytes` makes curl abort the transfer.
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        self.0.extend_from_slice(data);
+        if self.body.len() + data.len() > self.max_bytes {
+            self.exceeded = true;
+            return Ok(0);
+        }
+        self.body.extend_from_slice(data);
         Ok(data.len())
     }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        self.headers.push(String::from_utf8_lossy(data).to_string());
+        true
+    }
 }
 
-pub fn request(url: &str) -> VerifierResult<Vec<u8>> {
-    let mut easy = Easy2::new(Collector(Vec::new()));
-    easy.url(url)?;
-    easy.perform()?;
+/// The headers of a response, by name, along with the HTTP version of its
+/// status line, e.g. `HTTP/1.1`; they are read as a map of the headers.
+///
+/// Note: `curl` does not expose `CURLINFO_HTTP_VERSION`, so the version is
+/// read from the last status line received.
+#[derive(Clone, Default)]
+pub struct ResponseHeaders {
+    pub headers: HashMap<String, String>,
+    pub http_version: Option<String>,
+}
+impl Deref for ResponseHeaders {
+    type Target = HashMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.headers
+    }
+}
+
+/// A successful (200) response along with the timings captured for it.
+pub struct Response {
+    pub body: Vec<u8>,
+    pub time_to_first_byte: Duration,
+    /// The time taken by the whole request (`CURLINFO_TOTAL_TIME`).
+    pub total_time: Duration,
+    /// The `Content-Type` header of the response, if any.
+    pub content_type: Option<String>,
+}
+
+/// The sizes of a response requested with `Accept-Encoding: gzip`.
+pub struct Compression {
+    /// The `Content-Encoding` of the response, if any.
+    pub encoding: Option<String>,
+    /// The bytes of the body as transferred (`CURLINFO_SIZE_DOWNLOAD`).
+    pub transferred_bytes: usize,
+    /// The bytes of the body once decompressed.
+    pub decompressed_bytes: usize,
+}
+
+/// A request to be issued by the verifier: a GET of `url` or, when there is a
+/// `body`, a POST (or PUT) of it to `url` as JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub url: String,
+    pub method: HttpMethod,
+    pub body: Option<Vec<u8>>,
+    /// The `Accept` header to send, if any; see `ContentType::accept`.
+    pub accept: Option<&'static str>,
+}
+impl Request {
+    pub fn get(url: &str) -> Request {
+        Request {
+            url: url.to_string(),
+            method: HttpMethod::Get,
+            body: None,
+            accept: None,
+        }
+    }
+
+    pub fn with_body(url: &str, method: HttpMethod, body: Vec<u8>) -> Request {
+        Request {
+            url: url.to_string(),
+            method,
+            body: Some(body),
+            accept: None,
+        }
+    }
+
+    /// Sets the `Accept` header sent with this request.
+    pub fn accepting(mut self, accept: &'static str) -> Request {
+        self.accept = Some(accept);
+        self
+    }
+
+    /// Issues this request and returns the response body if it was a 200.
+    pub fn send(&self) -> VerifierResult<Vec<u8>> {
+        request_with_method(
+            &self.url,
+            self.method,
+            self.body.as_deref(),
+            &self.headers(),
+        )
+    }
+
+    /// Issues this request and returns the `Response` if it was a 200.
+    pub fn fetch(&self) -> VerifierResult<Response> {
+        fetch_with_method(
+            &self.url,
+            self.method,
+            self.body.as_deref(),
+            &self.headers(),
+        )
+    }
+
+    /// Gets the headers sent with this request.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if let Some(accept) = self.accept {
+            headers.push(("Accept".to_string(), accept.to_string()));
+        }
+        if self.body.is_some() {
+            headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        }
+        headers
+    }
+}
+
+/// Requests `url` with `method`, sending `body` (if any) and the additional
+/// `headers`, and returns the response body if it was a 200.
+///
+/// Note: this, `fetch_with_method`, and `get_response_headers` share the same
+/// underlying `perform`, through which every request the verifier makes goes;
+/// anything which should hold for all requests belongs there.
+pub fn request_with_method(
+    url: &str,
+    method: HttpMethod,
+    body: Option<&[u8]>,
+    headers: &[(String, String)],
+) -> VerifierResult<Vec<u8>> {
+    Ok(fetch_with_method(url, method, body, headers)?.body)
+}
+
+/// Like `request_with_method`, but returns the whole `Response`.
+pub fn fetch_with_method(
+    url: &str,
+    method: HttpMethod,
+    body: Option<&[u8]>,
+    headers: &[(String, String)],
+) -> VerifierResult<Response> {
+    let mut easy = perform(url, method, body, headers, None)?;
 
     match easy.response_code() {
-        Ok(200) => Ok(easy.get_ref().0.clone()),
-        Ok(code) => Err(Non200Response(url.to_string(), code)),
+        Ok(200) => Ok(Response {
+            body: std::mem::take(&mut easy.get_mut().body),
+            time_to_first_byte: easy.starttransfer_time()?,
+            total_time: easy.total_time()?,
+            content_type: easy.content_type()?.map(str::to_string),
+        }),
+        Ok(code) => Err(non_200_response(url, code, easy.get_ref())),
         Err(e) => Err(RequestError(url.to_string(), e.to_string())),
     }
 }
 
-pub fn get_response_body(url: &str, messages: &mut Messages) -> Option<String> {
+/// GETs `url`, sending the given `accept` header, and returns the response
+/// body, reporting any failure to `messages`.
+pub fn get_response_body(
+    url: &str,
+    accept: &'static str,
+    messages: &mut Messages,
+) -> Option<String> {
+    get_response_body_for(&Request::get(url).accepting(accept), messages)
+}
+
+/// Like `get_response_body`, but issues the given `request`.
+pub fn get_response_body_for(request: &Request, messages: &mut Messages) -> Option<String> {
+    let description = match &request.body {
+        Some(body) => format!(
+            "Sending {} to URL {}: {}",
+            request.method.as_ref(),
+            request.url,
+            String::from_utf8_lossy(body)
+        ),
+        None => format!("Accessing URL {}", request.url),
+    };
     log(
-        format!("Accessing URL {}", url).cyan(),
+        description.cyan(),
         LogOptions {
             border: None,
             border_bottom: None,
-            quiet: false,
+            level: LogLevel::Info,
         },
     );
 
-    match request(url) {
-        Ok(bytes) => Some(String::from_utf8_lossy(&*bytes).to_string()),
+    match request.fetch() {
+        Ok(response) => {
+            verify_time_to_first_byte(
+                response.time_to_first_byte,
+                config::max_time_to_first_byte(),
+                messages,
+            );
+            verify_declared_charset(&response, messages);
+            Some(String::from_utf8_lossy(&response.body).to_string())
+        }
         Err(e) => match e {
-            Non200Response(url, code) => {
-                messages.error(
-                    format!("Non-200 response from {}: {}", url, code),
-                    "Non-200 response",
-                );
+            Non200Response(..) => {
+                messages.error(e.to_string(), "Non-200 response");
+                None
+            }
+            UnexpectedRedirect(..) => {
+                messages.error(e.to_string(), "Unexpected redirect");
                 None
             }
             RequestError(url, err_string) => {
@@ -59,9 +297,13 @@ pub fn get_response_body(url: &str, messages: &mut Messages) -> Option<String> {
                 );
                 None
             }
+            ResponseTooLarge(..) => {
+                messages.error(e.to_string(), "Response too large");
+                None
+            }
             _ => {
                 messages.error(
-                    format!("Unknown error requesting {}: {:?}", url, e),
+                    format!("Unknown error requesting {}: {:?}", request.url, e),
                     "Unknown error",
                 );
                 None
@@ -70,35 +312,46 @@ pub fn get_response_body(url: &str, messages: &mut Messages) -> Option<String> {
     }
 }
 
+/// GETs `url`, sending the given `accept` header, and returns the response
+/// headers.
 pub fn get_response_headers(
     url: &str,
+    accept: &'static str,
+    messages: &mut Messages,
+) -> VerifierResult<ResponseHeaders> {
+    get_response_headers_for(&Request::get(url).accepting(accept), messages)
+}
+
+/// Like `get_response_headers`, but issues the given `request`.
+pub fn get_response_headers_for(
+    request: &Request,
     messages: &mut Messages,
-) -> VerifierResult<HashMap<String, String>> {
+) -> VerifierResult<ResponseHeaders> {
+    let url = request.url.as_str();
     let mut headers = HashMap::new();
-    let mut handle = Easy::new();
-    handle.url(url).unwrap();
-
-    let mut header_vec = Vec::new();
-    {
-        let mut transfer = handle.transfer();
-        transfer
-            .header_function(|header| {
-                header_vec.push(String::from_utf8_lossy(header).to_string());
-                true
-            })
-            .unwrap();
-        match transfer.perform() {
-            Ok(_) => {}
-            Err(e) => {
-                messages.error(
-                    format!("Error requesting headers for url: {}, {:?}", url, e),
-                    "Header(s) Error",
-                );
-                return Err(CurlError(e));
-            }
-        };
-    }
-    for header in header_vec {
+    let easy = match perform(
+        url,
+        request.method,
+        request.body.as_deref(),
+        &request.headers(),
+        None,
+    ) {
+        Ok(easy) => easy,
+        Err(CurlError(e)) => {
+            messages.error(
+                format!("Error requesting headers for url: {}, {:?}", url, e),
+                "Header(s) Error",
+            );
+            return Err(CurlError(e));
+        }
+        Err(e @ ResponseTooLarge(..)) => {
+            messages.error(e.to_string(), "Response too large");
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+
+    for header in &easy.get_ref().headers {
         let split: Vec<&str> = header.split(":").collect();
         if split.len() >= 2 {
             let key = split.get(0).unwrap().trim().to_string().clone();
@@ -107,7 +360,487 @@ pub fn get_response_headers(
         }
     }
 
-    Ok(headers)
+    Ok(ResponseHeaders {
+        headers,
+        http_version: status_line_version(&easy.get_ref().headers).map(str::to_string),
+    })
+}
+
+/// GETs `url`, sending the given `accept` header and offering to accept gzip,
+/// and returns the `Compression` of the response if it was a 200.
+pub fn get_compression(url: &str, accept: &'static str) -> VerifierResult<Compression> {
+    let request_headers = Request::get(url).accepting(accept).headers();
+    let mut easy = perform(url, HttpMethod::Get, None, &request_headers, Some("gzip"))?;
+
+    match easy.response_code() {
+        Ok(200) => {
+            let collector = easy.get_ref();
+            let encoding = header_value(&collector.headers, "Content-Encoding");
+            let decompressed_bytes = collector.body.len();
+
+            Ok(Compression {
+                encoding,
+                transferred_bytes: easy.download_size()? as usize,
+                decompressed_bytes,
+            })
+        }
+        Ok(code) => Err(non_200_response(url, code, easy.get_ref())),
+        Err(e) => Err(RequestError(url.to_string(), e.to_string())),
+    }
+}
+
+/// Checks that the server at `url` keeps its connection alive between
+/// requests, and warns if it does not.
+///
+/// Every benchmark sends `Connection: keep-alive`, so a server which closes
+/// the connection after each response is measured opening connections.
+///
+/// Note: two requests are made over a single handle which may reuse its
+/// connection; had the server closed the first, the second goes out from a
+/// different local port. (The safe `curl` wrapper does not expose
+/// `CURLINFO_NUM_CONNECTS`, so the local port stands in for it.) A
+/// `config::unix_socket` has no local port, so reuse is not checked through
+/// one.
+pub fn verify_connection_reuse(url: &str, messages: &mut Messages) {
+    if config::unix_socket().is_some() {
+        return;
+    }
+    let mut easy = Easy2::new(Collector::default());
+    let mut local_ports = Vec::new();
+    for _ in 0..2 {
+        match perform_on(&mut easy, url) {
+            Ok(port) => local_ports.push(port),
+            // Failed requests are reported by the verification proper.
+            Err(_) => return,
+        }
+    }
+    report_connection_reuse(local_ports[0] == local_ports[1], messages);
+}
+
+/// Sends `depth` requests for `url` down a single connection without waiting
+/// for any response, as the benchmark's `pipeline.lua` does, and returns the
+/// code and body of each response in the order they came back.
+///
+/// Note: `curl` no longer pipelines, so the requests are written to a plain
+/// TCP connection; an `https` url cannot be pipelined this way, and `None` is
+/// returned for it without sending anything.
+pub fn fetch_pipelined(
+    url: &str,
+    accept: &str,
+    depth: usize,
+) -> VerifierResult<Option<Vec<(u32, String)>>> {
+    let (scheme, _, _) = split_url(url);
+    if scheme == "https" {
+        return Ok(None);
+    }
+    let responses = pipeline(url, accept, depth);
+    record_requests(depth, responses.is_err());
+
+    responses.map(Some)
+}
+
+/// Gets the number of requests issued so far by this process, and the number
+/// of those which failed to get any response (e.g. timed out or refused).
+/// Responses with a non-200 code are not failures here.
+///
+/// Note: this is observational only; verification never depends on it.
+pub fn request_totals() -> (usize, usize) {
+    (
+        REQUESTS_ISSUED.load(Ordering::Relaxed),
+        REQUESTS_FAILED.load(Ordering::Relaxed),
+    )
+}
+
+/// Errors when the `Content-Type` of `response` declares `charset=utf-8` but
+/// its body is not valid UTF-8; e.g. Latin-1 text. Bodies are otherwise read
+/// lossily, which would hide the invalid bytes behind replacement characters.
+pub fn verify_declared_charset(response: &Response, messages: &mut Messages) {
+    let declares_utf8 = response
+        .content_type
+        .as_deref()
+        .is_some_and(|content_type| {
+            content_type
+                .to_lowercase()
+                .replace(' ', "")
+                .contains("charset=utf-8")
+        });
+    if let (true, Err(e)) = (declares_utf8, std::str::from_utf8(&response.body)) {
+        messages.error(
+            format!(
+                "Response body is not valid UTF-8 (at byte {}), though its Content-Type declares charset=utf-8.",
+                e.valid_up_to()
+            ),
+            "Invalid UTF-8",
+        );
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Counts `count` requests toward `request_totals`, as failures if `failed`.
+fn record_requests(count: usize, failed: bool) {
+    REQUESTS_ISSUED.fetch_add(count, Ordering::Relaxed);
+    if failed {
+        REQUESTS_FAILED.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+/// Sends the requests of `fetch_pipelined`.
+fn pipeline(url: &str, accept: &str, depth: usize) -> VerifierResult<Vec<(u32, String)>> {
+    let request_error = |e: String| RequestError(url.to_string(), e);
+    let (scheme, authority, path) = split_url(url);
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: {}\r\nConnection: keep-alive\r\n\r\n",
+        path,
+        authority,
+        config::user_agent(),
+        accept
+    );
+    let requests = request.repeat(depth);
+    let responses = match config::unix_socket() {
+        Some(socket) => {
+            let stream = UnixStream::connect(socket).map_err(|e| request_error(e.to_string()))?;
+            stream.set_read_timeout(Some(PIPELINE_TIMEOUT))?;
+            stream.set_write_timeout(Some(PIPELINE_TIMEOUT))?;
+            exchange(&stream, url, &requests, depth)
+        }
+        None => {
+            let stream = TcpStream::connect(socket_address(scheme, authority))
+                .map_err(|e| request_error(e.to_string()))?;
+            stream.set_read_timeout(Some(PIPELINE_TIMEOUT))?;
+            stream.set_write_timeout(Some(PIPELINE_TIMEOUT))?;
+            exchange(&stream, url, &requests, depth)
+        }
+    }?;
+    Ok(responses
+        .into_iter()
+        .map(|(code, body)| (code, String::from_utf8_lossy(&body).to_string()))
+        .collect())
+}
+
+/// Writes the `requests` for `url` to `stream` and reads `count` responses
+/// from it.
+fn exchange<S: Read + Write>(
+    mut stream: S,
+    url: &str,
+    requests: &str,
+    count: usize,
+) -> VerifierResult<Vec<(u32, Vec<u8>)>> {
+    stream.write_all(requests.as_bytes())?;
+
+    read_responses(&mut BufReader::new(stream), count).map_err(|e| RequestError(url.to_string(), e))
+}
+
+/// Performs the request for `url` regardless of the response code, and
+/// returns the handle from which the response can be read.
+///
+/// Note: given an `accept_encoding`, curl sends it and transparently
+/// decompresses the response body.
+fn perform(
+    url: &str,
+    method: HttpMethod,
+    body: Option<&[u8]>,
+    headers: &[(String, String)],
+    accept_encoding: Option<&str>,
+) -> VerifierResult<Easy2<Collector>> {
+    let mut easy = Easy2::new(Collector::default());
+    easy.url(url)?;
+    easy.useragent(&config::user_agent())?;
+    if let Some(path) = config::unix_socket() {
+        easy.unix_socket(&path)?;
+    }
+    if config::follow_redirects() {
+        easy.follow_location(true)?;
+    }
+    if let Some(accept_encoding) = accept_encoding {
+        easy.accept_encoding(accept_encoding)?;
+    }
+    if !headers.is_empty() {
+        let mut list = List::new();
+        for (name, value) in headers {
+            list.append(&format!("{}: {}", name, value))?;
+        }
+        easy.http_headers(list)?;
+    }
+    if let Some(body) = body {
+        easy.post_fields_copy(body)?;
+    }
+    if method == HttpMethod::Head {
+        // Otherwise curl would wait on a body which is never sent.
+        easy.nobody(true)?;
+    } else if method != HttpMethod::Get || body.is_some() {
+        easy.custom_request(method.as_ref())?;
+    }
+    let performed = easy.perform();
+    record_requests(1, performed.is_err());
+    if let Err(e) = performed {
+        let collector = easy.get_ref();
+        if collector.exceeded {
+            return Err(ResponseTooLarge(url.to_string(), collector.max_bytes));
+        }
+        return Err(e.into());
+    }
+    log_exchange(&mut easy, method, url, headers);
+
+    Ok(easy)
+}
+
+/// Gets the error for the response to `url` with the non-200 `code`; for a
+/// redirect, naming where it pointed, since a redirect is not followed unless
+/// `config::follow_redirects`.
+fn non_200_response(url: &str, code: u32, collector: &Collector) -> VerifierError {
+    match header_value(&collector.headers, "Location") {
+        Some(location) if (300..400).contains(&code) => {
+            UnexpectedRedirect(url.to_string(), location)
+        }
+        _ => Non200Response(url.to_string(), code, body_snippet(&collector.body)),
+    }
+}
+
+/// Gets the value of the header `name` from among the raw `headers`, matched
+/// case-insensitively; the last, should a redirect have been followed.
+fn header_value(headers: &[String], name: &str) -> Option<String> {
+    headers.iter().rev().find_map(|header| {
+        let (header_name, value) = header.split_once(':')?;
+        if header_name.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Gets the HTTP version from the last status line among the raw `headers`;
+/// earlier ones belong to interim responses such as `100 Continue`.
+fn status_line_version(headers: &[String]) -> Option<&str> {
+    headers
+        .iter()
+        .rev()
+        .find(|header| header.starts_with("HTTP/"))
+        .and_then(|status_line| status_line.split_whitespace().next())
+}
+
+/// Gets at most the first 200 characters of `body` for display in an error.
+fn body_snippet(body: &[u8]) -> String {
+    let body = String::from_utf8_lossy(body);
+    let body = body.trim();
+    match body.char_indices().nth(200) {
+        Some((end, _)) => format!("{} ...", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+/// Logs the raw request and response at `LogLevel::Debug`.
+fn log_exchange(
+    easy: &mut Easy2<Collector>,
+    method: HttpMethod,
+    url: &str,
+    headers: &[(String, String)],
+) {
+    let debug = || LogOptions {
+        border: None,
+        border_bottom: None,
+        level: LogLevel::Debug,
+    };
+    log(
+        format!("{} {} {:?}", method.as_ref(), url, headers).normal(),
+        debug(),
+    );
+    let code = easy.response_code().unwrap_or_default();
+    let collector = easy.get_ref();
+    let response_headers: Vec<&str> = collector
+        .headers
+        .iter()
+        .map(|header| header.trim())
+        .filter(|header| !header.is_empty())
+        .collect();
+    log(
+        format!("Response {}: {:?}", code, response_headers).normal(),
+        debug(),
+    );
+    log(String::from_utf8_lossy(&collector.body).normal(), debug());
+}
+
+/// Logs the time to first byte and warns if it exceeds `max`, when given.
+///
+/// Note: this is informational only; a slow first byte is worth knowing about
+/// but is not, in itself, a reason to fail verification.
+fn verify_time_to_first_byte(
+    time_to_first_byte: Duration,
+    max: Option<Duration>,
+    messages: &mut Messages,
+) {
+    log(
+        format!("Time to first byte: {}ms", time_to_first_byte.as_millis()).cyan(),
+        LogOptions {
+            border: None,
+            border_bottom: None,
+            level: LogLevel::Info,
+        },
+    );
+    if let Some(max) = max {
+        if exceeds_max_time_to_first_byte(time_to_first_byte, max) {
+            messages.warning(
+                format!(
+                    "Time to first byte of {}ms exceeds the maximum of {}ms.",
+                    time_to_first_byte.as_millis(),
+                    max.as_millis()
+                ),
+                "Slow First Byte",
+            );
+        }
+    }
+}
+
+fn exceeds_max_time_to_first_byte(time_to_first_byte: Duration, max: Duration) -> bool {
+    time_to_first_byte > max
+}
+
+/// GETs `url` with keep-alive over `easy` and returns the local port from
+/// which the request was sent.
+fn perform_on(easy: &mut Easy2<Collector>, url: &str) -> Result<u16, curl::Error> {
+    let mut headers = List::new();
+    headers.append("Connection: keep-alive")?;
+    easy.url(url)?;
+    easy.useragent(&config::user_agent())?;
+    easy.http_headers(headers)?;
+    easy.forbid_reuse(false)?;
+    if let Some(path) = config::unix_socket() {
+        easy.unix_socket(&path)?;
+    }
+    let performed = easy.perform();
+    record_requests(1, performed.is_err());
+    performed?;
+
+    easy.local_port()
+}
+
+fn report_connection_reuse(reused: bool, messages: &mut Messages) {
+    if !reused {
+        messages.warning(
+            "Server closed the connection after a response despite \"Connection: keep-alive\"; every request will require a new connection.",
+            "No Keep-Alive",
+        );
+    }
+}
+
+/// Splits `url` into its scheme (`http` when it has none), its authority and
+/// its path.
+fn split_url(url: &str) -> (&str, &str, &str) {
+    let (scheme, without_scheme) = match url.find("://") {
+        Some(index) => (&url[..index], &url[index + 3..]),
+        None => ("http", url),
+    };
+    match without_scheme.find('/') {
+        Some(index) => {
+            let (authority, path) = without_scheme.split_at(index);
+            (scheme, authority, path)
+        }
+        None => (scheme, without_scheme, "/"),
+    }
+}
+
+/// Gets the `host:port` to connect to for `authority`, which omits the port
+/// when it is the default for `scheme`.
+fn socket_address(scheme: &str, authority: &str) -> String {
+    // The host of an IPv6 authority is bracketed, and itself has colons.
+    let host_end = authority.rfind(']').map_or(0, |index| index + 1);
+    if authority[host_end..].contains(':') {
+        authority.to_string()
+    } else if scheme == "https" {
+        format!("{}:443", authority)
+    } else {
+        format!("{}:80", authority)
+    }
+}
+
+/// Reads `count` HTTP/1.1 responses from `reader`, and returns the code and
+/// body of each; bodies are delimited by either `Content-Length` or chunked
+/// `Transfer-Encoding`.
+fn read_responses<R: BufRead>(reader: &mut R, count: usize) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let mut responses = Vec::new();
+    while responses.len() < count {
+        let status_line = read_line(reader)?;
+        if status_line.is_empty() {
+            return Err(format!(
+                "connection closed after {} of {} responses",
+                responses.len(),
+                count
+            ));
+        }
+        let code = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u32>().ok())
+            .ok_or_else(|| format!("malformed status line: {:?}", status_line))?;
+
+        let mut content_length = 0;
+        let mut chunked = false;
+        loop {
+            let line = read_line(reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = value.trim().parse::<usize>().unwrap_or(0);
+                } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                    chunked = value.to_lowercase().contains("chunked");
+                }
+            }
+        }
+
+        let body = if chunked {
+            read_chunked_body(reader)?
+        } else {
+            read_exactly(reader, content_length)?
+        };
+        responses.push((code, body));
+    }
+
+    Ok(responses)
+}
+
+/// Reads a chunked body from `reader`, up to and including its trailers.
+fn read_chunked_body<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    loop {
+        let size_line = read_line(reader)?;
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| format!("malformed chunk size: {:?}", size_line))?;
+        if size == 0 {
+            while !read_line(reader)?.is_empty() {}
+            return Ok(body);
+        }
+        body.extend(read_exactly(reader, size)?);
+        read_line(reader)?;
+    }
+}
+
+/// Reads a line from `reader` without its line ending; empty at the end.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Reads exactly `length` bytes from `reader`; errors without reading any if
+/// `length` exceeds `config::max_response_bytes`, as a bogus `Content-Length`
+/// or chunk size would otherwise be allocated as is.
+fn read_exactly<R: BufRead>(reader: &mut R, length: usize) -> Result<Vec<u8>, String> {
+    let max_bytes = config::max_response_bytes();
+    if length > max_bytes {
+        return Err(format!(
+            "response length of {} bytes exceeds the maximum of {}",
+            length, max_bytes
+        ));
+    }
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
 }
 
 //
@@ -116,19 +849,252 @@ pub fn get_response_headers(
 
 #[cfg(test)]
 mod tests {
-    use crate::request::get_response_headers;
+    use crate::request::{
+        body_snippet, exceeds_max_time_to_first_byte, exchange, fetch_pipelined,
+        get_response_headers, non_200_response, read_responses, report_connection_reuse,
+        socket_address, split_url, status_line_version, verify_declared_charset, Collector,
+        ContentType, HttpMethod, Request, Response,
+    };
     use crate::verification::Messages;
+    use curl::easy::Handler;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
 
     #[test]
     fn what_headers() {
         let url = "http://www.google.com";
         let mut messages = Messages::new(url);
-        let serialized = get_response_headers(url, &mut messages).unwrap();
+        let serialized =
+            get_response_headers(url, ContentType::Json.accept(), &mut messages).unwrap();
 
-        for header in serialized {
+        for header in serialized.headers {
             if header.0 == "Vary" {
                 assert_eq!(header.1, "Accept-Encoding".to_string());
             }
         }
     }
+
+    #[test]
+    fn it_should_compare_time_to_first_byte_against_max() {
+        let max = Duration::from_millis(100);
+        assert!(exceeds_max_time_to_first_byte(
+            Duration::from_millis(101),
+            max
+        ));
+        assert!(!exceeds_max_time_to_first_byte(
+            Duration::from_millis(100),
+            max
+        ));
+        assert!(!exceeds_max_time_to_first_byte(
+            Duration::from_millis(5),
+            max
+        ));
+    }
+
+    #[test]
+    fn it_should_name_http_methods() {
+        assert_eq!(HttpMethod::Get.as_ref(), "GET");
+        assert_eq!(HttpMethod::Post.as_ref(), "POST");
+        assert_eq!(HttpMethod::Put.as_ref(), "PUT");
+    }
+
+    #[test]
+    fn it_should_send_json_content_type_with_body() {
+        let get = Request::get("http://tfb-server:8080/updates");
+        let put = Request::with_body("http://tfb-server:8080/updates", HttpMethod::Put, vec![]);
+
+        assert!(get.headers().is_empty());
+        assert_eq!(
+            put.headers(),
+            vec![("Content-Type".to_string(), "application/json".to_string())]
+        );
+    }
+
+    #[test]
+    fn it_should_warn_when_connection_is_not_reused() {
+        let mut messages = Messages::default();
+        report_connection_reuse(true, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        report_connection_reuse(false, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("Server closed the connection"));
+    }
+
+    #[test]
+    fn it_should_read_http_version_from_last_status_line() {
+        let headers = vec![
+            "HTTP/1.1 100 Continue\r\n".to_string(),
+            "\r\n".to_string(),
+            "HTTP/1.0 200 OK\r\n".to_string(),
+            "Server: tfb\r\n".to_string(),
+        ];
+        assert_eq!(status_line_version(&headers), Some("HTTP/1.0"));
+        assert_eq!(status_line_version(&[]), None);
+    }
+
+    #[test]
+    fn it_should_name_the_location_of_an_unexpected_redirect() {
+        let collector = Collector {
+            headers: vec![
+                "HTTP/1.1 301 Moved Permanently".to_string(),
+                "location: http://tfb-server:8080/json/".to_string(),
+                "Content-Length: 0".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            non_200_response("http://tfb-server:8080/json", 301, &collector).to_string(),
+            "Unexpected redirect from http://tfb-server:8080/json to http://tfb-server:8080/json/"
+        );
+
+        // Without a `Location`, it is as any other non-200.
+        let collector = Collector {
+            body: b"Moved".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(
+            non_200_response("http://tfb-server:8080/json", 302, &collector).to_string(),
+            "Non-200 response from http://tfb-server:8080/json: 302; body: Moved"
+        );
+    }
+
+    #[test]
+    fn it_should_stop_collecting_beyond_max_bytes() {
+        let mut collector = Collector {
+            max_bytes: 8,
+            ..Default::default()
+        };
+        assert_eq!(collector.write(b"Hello").unwrap(), 5);
+        assert!(!collector.exceeded);
+
+        assert_eq!(collector.write(b", World!").unwrap(), 0);
+        assert!(collector.exceeded);
+        assert_eq!(collector.body, b"Hello".to_vec());
+    }
+
+    #[test]
+    fn it_should_truncate_body_snippet() {
+        assert_eq!(
+            body_snippet(b"  Internal Server Error\n"),
+            "Internal Server Error"
+        );
+
+        let long = "フ".repeat(250);
+        assert_eq!(
+            body_snippet(long.as_bytes()),
+            format!("{} ...", "フ".repeat(200))
+        );
+    }
+
+    #[test]
+    fn it_should_split_url() {
+        assert_eq!(
+            split_url("http://tfb-server:8080/plaintext"),
+            ("http", "tfb-server:8080", "/plaintext")
+        );
+        assert_eq!(split_url("http://[::1]:8080"), ("http", "[::1]:8080", "/"));
+    }
+
+    #[test]
+    fn it_should_split_url_without_a_port() {
+        assert_eq!(
+            split_url("http://tfb-server/plaintext"),
+            ("http", "tfb-server", "/plaintext")
+        );
+        assert_eq!(
+            split_url("https://tfb-server/plaintext"),
+            ("https", "tfb-server", "/plaintext")
+        );
+    }
+
+    #[test]
+    fn it_should_default_the_socket_address_port_for_the_scheme() {
+        assert_eq!(socket_address("http", "tfb-server"), "tfb-server:80");
+        assert_eq!(socket_address("https", "tfb-server"), "tfb-server:443");
+        assert_eq!(socket_address("http", "[::1]"), "[::1]:80");
+        assert_eq!(socket_address("http", "[::1]:8080"), "[::1]:8080");
+        assert_eq!(
+            socket_address("https", "tfb-server:8443"),
+            "tfb-server:8443"
+        );
+    }
+
+    #[test]
+    fn it_should_not_pipeline_https() {
+        let responses = fetch_pipelined("https://tfb-server/plaintext", "text/plain", 16);
+        assert!(matches!(responses, Ok(None)));
+    }
+
+    #[test]
+    fn it_should_read_pipelined_responses() {
+        let stream = concat!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, World!",
+            "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n",
+            "5\r\nHello\r\n8\r\n, World!\r\n0\r\n\r\n",
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n",
+        );
+        let responses = read_responses(&mut stream.as_bytes(), 3).unwrap();
+        assert_eq!(
+            responses,
+            vec![
+                (200, b"Hello, World!".to_vec()),
+                (200, b"Hello, World!".to_vec()),
+                (500, Vec::new()),
+            ]
+        );
+
+        assert_eq!(
+            read_responses(&mut stream.as_bytes(), 4).unwrap_err(),
+            "connection closed after 3 of 4 responses"
+        );
+    }
+
+    #[test]
+    fn it_should_not_read_responses_longer_than_the_maximum() {
+        let stream = "HTTP/1.1 200 OK\r\nContent-Length: 1099511627776\r\n\r\nHello, World!";
+        assert!(read_responses(&mut stream.as_bytes(), 1)
+            .unwrap_err()
+            .starts_with("response length of 1099511627776 bytes exceeds the maximum of "));
+    }
+
+    #[test]
+    fn it_should_exchange_pipelined_requests_over_a_unix_socket() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nokHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+            .unwrap();
+        let responses =
+            exchange(&client, "http://localhost/plaintext", "GET /\r\n\r\n", 2).unwrap();
+        assert_eq!(
+            responses,
+            vec![(200, b"ok".to_vec()), (200, b"ok".to_vec())]
+        );
+
+        let mut sent = [0; 9];
+        server.read_exact(&mut sent).unwrap();
+        assert_eq!(&sent, b"GET /\r\n\r\n");
+    }
+
+    #[test]
+    fn it_should_error_on_invalid_utf8_declared_as_utf8() {
+        let latin1 = |content_type: &str| Response {
+            body: b"caf\xe9".to_vec(),
+            time_to_first_byte: Duration::default(),
+            total_time: Duration::default(),
+            content_type: Some(content_type.to_string()),
+        };
+        let mut messages = Messages::default();
+        verify_declared_charset(&latin1("text/html; charset=ISO-8859-1"), &mut messages);
+        assert!(messages.errors.is_empty());
+
+        verify_declared_charset(&latin1("text/html; charset=UTF-8"), &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("not valid UTF-8 (at byte 3)"));
+    }
 }
@@ -1,25 +1,36 @@
 use crate::benchmark::BenchmarkCommands;
+use crate::config;
 use crate::database::DatabaseInterface;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::Executor;
+use crate::explain::ExpectedCounts;
+use crate::request::{
+    fetch_with_method, get_response_body, get_response_headers, verify_connection_reuse,
+    ContentType, HttpMethod, Request,
+};
+use crate::test_type::{verify_response_size, Executor};
 use crate::verification::Messages;
 use html5ever::tendril::*;
-use html5ever::tokenizer::Token::{CharacterTokens, DoctypeToken, TagToken};
+use html5ever::tokenizer::Token::{
+    CharacterTokens, CommentToken, DoctypeToken, EOFToken, NullCharacterToken, ParseError, TagToken,
+};
 use html5ever::tokenizer::{
     BufferQueue, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
 };
 use std::cmp::min;
+use std::collections::HashMap;
+use std::thread::sleep;
 
 const FORTUNES: &str = "<!doctype html><html><head><title>Fortunes</title></head><body><table><tr><th>id</th><th>message</th></tr><tr><td>11</td><td>&lt;script&gt;alert(&quot;This should not be displayed in a browser alert box.&quot;);&lt;/script&gt;</td></tr><tr><td>4</td><td>A bad random number generator: 1, 1, 1, 1, 1, 4.33e+67, 1, 1, 1</td></tr><tr><td>5</td><td>A computer program does what you tell it to do, not what you want it to do.</td></tr><tr><td>2</td><td>A computer scientist is someone who fixes things that aren&apos;t broken.</td></tr><tr><td>8</td><td>A list is only as strong as its weakest link. — Donald Knuth</td></tr><tr><td>0</td><td>Additional fortune added at request time.</td></tr><tr><td>3</td><td>After enough decimal places, nobody gives a damn.</td></tr><tr><td>7</td><td>Any program that runs right is obsolete.</td></tr><tr><td>10</td><td>Computers make very fast, very accurate mistakes.</td></tr><tr><td>6</td><td>Emacs is a nice operating system, but I prefer UNIX. — Tom Christaensen</td></tr><tr><td>9</td><td>Feature: A bug with seniority.</td></tr><tr><td>1</td><td>fortune: No such file or directory</td></tr><tr><td>12</td><td>フレームワークのベンチマーク</td></tr></table></body></html>";
 
 pub struct Fortune {
     pub concurrency_levels: Vec<u32>,
+    /// The times each burst of requests is sent while counting queries.
+    pub repetitions: u32,
     pub database_verifier: Box<dyn DatabaseInterface>,
 }
 impl Executor for Fortune {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "fortune"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -38,22 +49,60 @@ impl Executor for Fortune {
         })
     }
 
+    fn expected_counts(&self) -> Option<ExpectedCounts> {
+        Some(self.counts())
+    }
+
+    /// The `FORTUNES`, then those expected once `verify` has inserted one
+    /// thousand more; equal messages may be in any order.
+    fn expected_bodies(&self) -> Vec<String> {
+        let mut added_fortunes = String::new();
+        for i in 0..1_000 {
+            added_fortunes.push_str(&format!(
+                "<tr><td>{}</td><td>フレームワークのベンチマーク</td></tr>",
+                i + 13
+            ));
+        }
+
+        vec![
+            FORTUNES.to_string(),
+            FORTUNES.replace("</table>", &format!("{}</table>", added_fortunes)),
+        ]
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Html)
+    }
+
     /// Parses the given HTML string and asks the FortuneHTMLParser whether
     /// the parsed string is a valid fortune response.
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
+        self.database_verifier.verify_schema(&mut messages);
 
         // Initialization for query counting
-        let repetitions = 2;
-        let concurrency = *self.concurrency_levels.iter().max().unwrap();
-        let expected_queries = repetitions * concurrency;
-        let expected_rows = 12 * expected_queries;
-
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        let ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows,
+            ..
+        } = self.counts();
+
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Html.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
+            if !verify_html_content_type(&response_headers, &mut messages) {
+                return Ok(messages);
+            }
             self.verify_headers(&response_headers, &url, ContentType::Html, &mut messages);
+            verify_connection_reuse(url, &mut messages);
+            verify_benchmark_representation(url, &mut messages);
 
-            if let Some(response_body) = get_response_body(&url, &mut messages) {
+            if let Some(response_body) =
+                get_response_body(&url, ContentType::Html.accept(), &mut messages)
+            {
                 let mut accumulator = String::new();
                 for line in response_body.lines() {
                     accumulator.push_str(line);
@@ -62,7 +111,7 @@ impl Executor for Fortune {
 
                 if self.verify_fortune(&response_body, &mut messages) {
                     self.database_verifier.verify_queries_count(
-                        url,
+                        &Request::get(url),
                         "fortune",
                         concurrency,
                         repetitions,
@@ -70,7 +119,7 @@ impl Executor for Fortune {
                         &mut messages,
                     );
                     self.database_verifier.verify_rows_count(
-                        url,
+                        &Request::get(url),
                         "fortune",
                         concurrency,
                         repetitions,
@@ -80,6 +129,9 @@ impl Executor for Fortune {
                     );
 
                     self.verify_fortunes_are_dynamically_sized(&url, &mut messages);
+                    if config::fortune_cleanup() {
+                        self.database_verifier.reset_fortune_table(&mut messages);
+                    }
                 }
             }
         }
@@ -88,13 +140,29 @@ impl Executor for Fortune {
     }
 }
 impl Fortune {
+    /// Every request selects all 12 fortunes with one query; the requests are
+    /// made at the highest of the `concurrency_levels`.
+    fn counts(&self) -> ExpectedCounts {
+        let repetitions = self.repetitions;
+        let concurrency = *self.concurrency_levels.iter().max().unwrap();
+        let expected_queries = repetitions * concurrency;
+
+        ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows: 12 * expected_queries,
+            expected_updates: None,
+        }
+    }
+
     fn get_wrk_command(&self, url: &str, duration: u32, concurrency: u32) -> Vec<String> {
         vec![
             "wrk",
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Html.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -107,7 +175,10 @@ impl Fortune {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
     }
 
     /// Returns whether the HTML input parsed by this parser is valid against
@@ -131,6 +202,7 @@ impl Fortune {
             );
             false
         } else {
+            verify_response_size("fortune", response_body.len(), FORTUNES.len(), messages);
             true
         }
     }
@@ -154,43 +226,39 @@ impl Fortune {
         // database for the fortune table (now with 1,000 more random rows),
         // and create our view here. We can then check string equality with
         // the test's fortune implementation.
-        self.database_verifier.insert_one_thousand_fortunes();
-        let mut more_fortunes = String::from("<!doctype html><html><head><title>Fortunes</title></head><body><table><tr><th>id</th><th>message</th></tr><tr><td>11</td><td>&lt;script&gt;alert(&quot;This should not be displayed in a browser alert box.&quot;);&lt;/script&gt;</td></tr><tr><td>4</td><td>A bad random number generator: 1, 1, 1, 1, 1, 4.33e+67, 1, 1, 1</td></tr><tr><td>5</td><td>A computer program does what you tell it to do, not what you want it to do.</td></tr><tr><td>2</td><td>A computer scientist is someone who fixes things that aren&apos;t broken.</td></tr><tr><td>8</td><td>A list is only as strong as its weakest link. — Donald Knuth</td></tr><tr><td>0</td><td>Additional fortune added at request time.</td></tr><tr><td>3</td><td>After enough decimal places, nobody gives a damn.</td></tr><tr><td>7</td><td>Any program that runs right is obsolete.</td></tr><tr><td>10</td><td>Computers make very fast, very accurate mistakes.</td></tr><tr><td>6</td><td>Emacs is a nice operating system, but I prefer UNIX. — Tom Christaensen</td></tr><tr><td>9</td><td>Feature: A bug with seniority.</td></tr><tr><td>1</td><td>fortune: No such file or directory</td></tr><tr><td>12</td><td>フレームワークのベンチマーク</td></tr>");
-        for i in 0..1_000 {
-            more_fortunes.push_str(&format!(
-                "<tr><td>{}</td><td>フレームワークのベンチマーク</td></tr>",
-                i + 13
-            ));
-        }
-        more_fortunes.push_str("</table></body></html>");
+        self.database_verifier
+            .insert_one_thousand_fortunes(messages);
+        sleep(config::fortune_settle_delay());
+        let more_fortunes = fortune_rows(&self.expected_bodies()[1]);
 
-        if let Some(response_body) = get_response_body(&url, messages) {
+        if let Some(response_body) = get_response_body(&url, ContentType::Html.accept(), messages) {
             let mut accumulator = String::new();
             for line in response_body.lines() {
                 accumulator.push_str(line);
             }
-            let min_len = 75;
-            if accumulator.len() >= min_len {
-                // truncate the single-line for rendering
-                accumulator = accumulator[..min_len].to_string();
-                accumulator.push_str(" ... ");
-                messages.body(&accumulator);
-            }
+            // truncate the single-line for rendering
+            messages.body(&truncate_for_display(&accumulator, 75));
 
-            let fortunes = normalize_html(&response_body);
+            let fortunes = fortune_rows(&normalize_html(&response_body));
 
             // We explicitly *do not* check that the strings are equal here because
             // of how different implementations will order equal strings. E.g. we
             // added a bunch of copies of the last fortune above, and we order by
             // that column - it is valid to put them in any order because they are
-            // all equal. Instead, after normalizing both, we check that we have
-            // the same character count.
-            if fortunes.chars().count() != more_fortunes.chars().count() {
+            // all equal. Instead, we compare the sorted `(id, message)` rows, so a
+            // dropped fortune is caught even when the lengths happen to coincide.
+            if fortunes != more_fortunes {
+                let missing = more_fortunes
+                    .iter()
+                    .find(|row| !fortunes.contains(row))
+                    .map(|(id, message)| format!("; missing id {}: \"{}\"", id, message))
+                    .unwrap_or_default();
                 messages.error(
                     format!(
-                        "Fortunes not dynamically sized. Expected length: {}; actual length: {}",
+                        "Fortunes not dynamically sized. Expected rows: {}; actual rows: {}{}",
                         more_fortunes.len(),
-                        fortunes.len()
+                        fortunes.len(),
+                        missing
                     ),
                     "Non-dynamic Fortune",
                 );
@@ -199,6 +267,11 @@ impl Fortune {
     }
 }
 
+/// Normalizes the tokens of a fortunes page into `accumulator`.
+///
+/// Comments and null characters are kept as they were sent rather than
+/// dropped; since `FORTUNES` has neither, a page carrying either one fails to
+/// match it instead of passing unnoticed.
 struct FortunesAccumulator<'accum> {
     accumulator: &'accum mut String,
 }
@@ -225,7 +298,13 @@ impl<'accum> TokenSink for FortunesAccumulator<'accum> {
                     self.accumulator.push_str(&format!("</{}>", tag.name));
                 }
             },
-            _ => {}
+            CommentToken(comment) => {
+                self.accumulator.push_str(&format!("<!--{}-->", comment));
+            }
+            NullCharacterToken => self.accumulator.push('\0'),
+            // Malformed markup still yields the tokens above, which are what
+            // is compared.
+            ParseError(_) | EOFToken => {}
         }
         TokenSinkResult::Continue
     }
@@ -235,6 +314,103 @@ impl<'accum> TokenSink for FortunesAccumulator<'accum> {
 // PRIVATES
 //
 
+/// Errors unless the response `headers` declare an HTML `Content-Type`, and
+/// returns whether they do. Any other (e.g. an error served as JSON) means the
+/// endpoint is misconfigured, which comparing its body with `FORTUNES` would
+/// only obscure; the charset is left to `verify_headers`.
+fn verify_html_content_type(headers: &HashMap<String, String>, messages: &mut Messages) -> bool {
+    let content_type = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.as_str());
+    let is_html = is_html(content_type);
+    if !is_html {
+        messages.error(
+            format!(
+                "Fortunes response is not HTML (Content-Type: {}); not verifying further",
+                content_type.unwrap_or("none")
+            ),
+            "Invalid Content-Type",
+        );
+    }
+
+    is_html
+}
+
+/// Whether `content_type` is of the `text/html` media type, whatever its
+/// parameters.
+fn is_html(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|content_type| {
+        content_type
+            .split(';')
+            .next()
+            .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("text/html"))
+    })
+}
+
+/// Requests `url` with the `Accept` header of the benchmark commands and
+/// warns unless it is still answered with HTML; see
+/// `report_benchmark_representation`.
+fn verify_benchmark_representation(url: &str, messages: &mut Messages) {
+    let accept = ContentType::Html.benchmark_accept();
+    let headers = [("Accept".to_string(), accept.clone())];
+    if let Ok(response) = fetch_with_method(url, HttpMethod::Get, None, &headers) {
+        report_benchmark_representation(response.content_type.as_deref(), &accept, messages);
+    }
+}
+
+/// Warns unless the `content_type` answering the benchmark's `accept` is
+/// HTML. The benchmark favours `application/json`, so a framework which
+/// honours it naively would be benchmarked on another representation than
+/// was verified.
+fn report_benchmark_representation(
+    content_type: Option<&str>,
+    accept: &str,
+    messages: &mut Messages,
+) {
+    if !is_html(content_type) {
+        messages.warning(
+            format!(
+                "Response to the benchmark's Accept header ({}) is not HTML (Content-Type: {}); the benchmark would not measure the verified response",
+                accept,
+                content_type.unwrap_or("none")
+            ),
+            "Content Negotiation",
+        );
+    }
+}
+
+/// Truncates `body` to at most `max_len` bytes without splitting a character,
+/// marking with " ... " where anything was cut off.
+fn truncate_for_display(body: &str, max_len: usize) -> String {
+    if body.len() <= max_len {
+        return body.to_string();
+    }
+    let mut end = max_len;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{} ... ", &body[..end])
+}
+
+/// Gets the sorted `(id, message)` cells of each row in `fortunes`, which is
+/// normalized HTML; the header row has no `<td>` cells and is left out.
+fn fortune_rows(fortunes: &str) -> Vec<(String, String)> {
+    let mut rows: Vec<(String, String)> = fortunes
+        .split("<tr>")
+        .filter_map(|row| {
+            let mut cells = row
+                .split("<td>")
+                .skip(1)
+                .map(|cell| cell.split("</td>").next().unwrap_or_default().to_string());
+            Some((cells.next()?, cells.next()?))
+        })
+        .collect();
+    rows.sort();
+    rows
+}
+
 /// Normalizes the input HTML to the format present in the `FORTUNES` const.
 fn normalize_html(input: &str) -> String {
     let mut fortune_accumulator = String::new();
@@ -332,8 +508,59 @@ fn normalize_text(input: &str) -> String {
 #[cfg(test)]
 mod tests {
     use crate::database::mysql::Mysql;
-    use crate::test_type::fortune::{normalize_text, Fortune, FORTUNES};
+    use crate::request::{ContentType, Request};
+    use crate::test_type::fortune::{
+        fortune_rows, normalize_html, normalize_text, report_benchmark_representation,
+        truncate_for_display, verify_html_content_type, Fortune, FORTUNES,
+    };
+    use crate::test_type::Executor;
     use crate::verification::Messages;
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_should_warn_unless_the_benchmark_accept_header_gets_html() {
+        let accept = ContentType::Html.accept();
+        let mut messages = Messages::default();
+        report_benchmark_representation(Some("text/html; charset=utf-8"), accept, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let mut messages = Messages::default();
+        report_benchmark_representation(Some("application/json"), accept, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("is not HTML (Content-Type: application/json)"));
+    }
+
+    #[test]
+    fn it_should_only_accept_an_html_content_type() {
+        for content_type in [
+            "text/html; charset=utf-8",
+            "TEXT/HTML",
+            "text/html;charset=latin1",
+        ] {
+            let headers = HashMap::from([("content-type".to_string(), content_type.to_string())]);
+            let mut messages = Messages::default();
+            assert!(
+                verify_html_content_type(&headers, &mut messages),
+                "{}",
+                content_type
+            );
+            assert!(messages.errors.is_empty());
+        }
+
+        let headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        let mut messages = Messages::default();
+        assert!(!verify_html_content_type(&headers, &mut messages));
+        assert_eq!(
+            messages.errors[0].message,
+            "Fortunes response is not HTML (Content-Type: application/json); not verifying further"
+        );
+
+        let mut messages = Messages::default();
+        assert!(!verify_html_content_type(&HashMap::new(), &mut messages));
+        assert_eq!(messages.errors.len(), 1);
+    }
 
     #[test]
     fn it_should_pass_with_identity_fortunes() {
@@ -341,12 +568,30 @@ mod tests {
         let valid = FORTUNES;
         let fortune = Fortune {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            repetitions: 2,
             database_verifier: Box::new(Mysql {}),
         };
 
         fortune.verify_fortune(valid, &mut messages);
     }
 
+    #[test]
+    fn it_should_not_drop_comments_in_fortunes() {
+        let commented =
+            FORTUNES.replace("<tr><td>4</td>", "<!-- cached at 12:00 --><tr><td>4</td>");
+        assert!(normalize_html(&commented).contains("<!-- cached at 12:00 -->"));
+
+        let mut messages = Messages::default();
+        let fortune = Fortune {
+            concurrency_levels: vec![16, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        assert!(!fortune.verify_fortune(&commented, &mut messages));
+        assert_eq!(messages.errors.len(), 1);
+        assert!(fortune.verify_fortune(FORTUNES, &mut Messages::default()));
+    }
+
     #[test]
     fn it_should_normalize_lt_and_gt() {
         let good = "&lt;script&gt;";
@@ -437,4 +682,111 @@ mod tests {
         normalized = normalize_text("&#x28;&#x29;");
         assert_eq!(normalized, good);
     }
+
+    #[test]
+    fn it_should_not_truncate_short_body() {
+        assert_eq!(truncate_for_display("<p>ok</p>!", 75), "<p>ok</p>!");
+    }
+
+    #[test]
+    fn it_should_truncate_on_char_boundary() {
+        // Each of these characters is 3 bytes, so byte 75 lands mid-character.
+        let body = format!("{}フレームワーク", "a".repeat(73));
+        let truncated = truncate_for_display(&body, 75);
+
+        assert_eq!(truncated, format!("{} ... ", "a".repeat(73)));
+    }
+
+    #[test]
+    fn it_should_verify_with_the_benchmark_accept_header() {
+        let url = "http://tfb-server:8080/fortune";
+        let wrk_command = Fortune {
+            concurrency_levels: vec![16, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        }
+        .get_wrk_command(url, 15, 16);
+        let (_, accept) = Request::get(url)
+            .accepting(ContentType::Html.accept())
+            .headers()
+            .into_iter()
+            .find(|(name, _)| name == "Accept")
+            .unwrap();
+
+        assert!(wrk_command.contains(&format!("Accept: {}", accept)));
+    }
+
+    #[test]
+    fn it_should_record_additional_bytes_for_valid_fortunes() {
+        let mut messages = Messages::default();
+        let fortune = Fortune {
+            concurrency_levels: vec![16, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let spaced = FORTUNES.replace("<tr>", "\n  <tr>");
+
+        assert!(fortune.verify_fortune(&spaced, &mut messages));
+        assert_eq!(
+            messages.extra_bytes,
+            vec![("fortune".to_string(), spaced.len() - FORTUNES.len())]
+        );
+    }
+
+    #[test]
+    fn it_should_expect_twelve_rows_per_query() {
+        let fortune = Fortune {
+            concurrency_levels: vec![16, 512, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let counts = fortune.expected_counts().unwrap();
+        assert_eq!(counts.concurrency, 512);
+        assert_eq!(counts.repetitions, 2);
+        assert_eq!(counts.expected_queries, 2 * 512);
+        assert_eq!(counts.expected_rows, 12 * 2 * 512);
+        assert_eq!(counts.expected_updates, None);
+    }
+
+    #[test]
+    fn it_should_compare_fortune_rows_regardless_of_order() {
+        let rows = fortune_rows(FORTUNES);
+        assert_eq!(rows.len(), 13);
+        assert_eq!(
+            rows[0],
+            (
+                "0".to_string(),
+                "Additional fortune added at request time.".to_string()
+            )
+        );
+
+        let reordered = FORTUNES.replace(
+            "<tr><td>9</td><td>Feature: A bug with seniority.</td></tr><tr><td>1</td><td>fortune: No such file or directory</td></tr>",
+            "<tr><td>1</td><td>fortune: No such file or directory</td></tr><tr><td>9</td><td>Feature: A bug with seniority.</td></tr>",
+        );
+        assert_ne!(reordered, FORTUNES);
+        assert_eq!(fortune_rows(&reordered), rows);
+    }
+
+    #[test]
+    fn it_should_notice_a_dropped_fortune_of_the_same_length() {
+        let swapped = FORTUNES.replace(
+            "<tr><td>9</td><td>Feature: A bug with seniority.</td></tr>",
+            "<tr><td>9</td><td>Feature: A bug with seniority!</td></tr>",
+        );
+        assert_eq!(swapped.chars().count(), FORTUNES.chars().count());
+        assert_ne!(fortune_rows(&swapped), fortune_rows(FORTUNES));
+    }
+
+    #[test]
+    fn it_should_expect_the_added_fortunes_once_inserted() {
+        let fortune = Fortune {
+            database_verifier: Box::new(Mysql {}),
+            concurrency_levels: vec![16, 32],
+            repetitions: 2,
+        };
+        let bodies = fortune.expected_bodies();
+        assert_eq!(bodies[0], FORTUNES);
+        assert_eq!(fortune_rows(&bodies[1]).len(), 1_013);
+    }
 }
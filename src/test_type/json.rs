@@ -1,17 +1,25 @@
 use crate::benchmark::BenchmarkCommands;
+use crate::config;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::Executor;
+use crate::request::{
+    get_response_body, get_response_headers, verify_connection_reuse, ContentType,
+};
+use crate::test_type::{
+    invalid_json_message, parse_json_with_lowercase_keys, verify_chunked_encoding,
+    verify_conditional_headers, verify_head, verify_no_byte_order_mark, Executor, UTF8_BOM,
+};
 use crate::verification::Messages;
-use serde_json::Value;
+use serde_json::{json, Deserializer, Value};
 use std::cmp::min;
 
 pub struct Json {
     pub concurrency_levels: Vec<u32>,
+    /// The value of `message` which must be responded with.
+    pub expected_message: String,
 }
 impl Executor for Json {
-    fn wait_for_database_to_be_available(&self) {
-        // No database; intentionally blank.
+    fn name(&self) -> &'static str {
+        "json"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -30,16 +38,42 @@ impl Executor for Json {
         })
     }
 
+    fn expected_bodies(&self) -> Vec<String> {
+        vec![self.expected_body()]
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Json)
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
 
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Json.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
             self.verify_headers(&response_headers, &url, ContentType::Json, &mut messages);
-            if let Some(response_body) = get_response_body(&url, &mut messages) {
+            verify_connection_reuse(url, &mut messages);
+            if config::warn_on_conditional_headers() {
+                verify_conditional_headers(&response_headers, &mut messages);
+            }
+            if config::check_head() {
+                verify_head(
+                    url,
+                    ContentType::Json.accept(),
+                    &response_headers,
+                    &mut messages,
+                );
+            }
+            if let Some(response_body) =
+                get_response_body(&url, ContentType::Json.accept(), &mut messages)
+            {
                 messages.body(&response_body);
 
+                verify_no_byte_order_mark(&response_body, &mut messages);
                 self.verify_json(&response_body, &mut messages);
+                verify_chunked_encoding(&response_headers, response_body.len(), &mut messages);
             }
         }
 
@@ -53,7 +87,7 @@ impl Json {
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Json.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -66,28 +100,47 @@ impl Json {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
+    }
+
+    /// The minimal body; `{"message":"Hello, World!"}` by default.
+    fn expected_body(&self) -> String {
+        json!({ "message": self.expected_message }).to_string()
     }
 
     fn verify_json(&self, response_body: &str, messages: &mut Messages) {
-        if response_body.len() > 27 {
+        let expected_len = self.expected_body().len();
+        messages.extra_bytes("json", response_body.len().saturating_sub(expected_len));
+        if response_body.len() > expected_len {
             messages.warning(
                 format!(
                     "{} additional response byte(s) found. Consider removing unnecessary whitespace.",
-                    (response_body.len() - 27)
+                    (response_body.len() - expected_len)
                 ),
                 "Additional response byte(s)"
             );
         }
 
-        match serde_json::from_str::<Value>(&response_body.to_lowercase()) {
+        if !verify_single_json_document(response_body, messages) {
+            return;
+        }
+        match parse_json_with_lowercase_keys(response_body) {
             Err(e) => {
-                messages.error(format!("Invalid JSON: {:?}", e), "Invalid JSON");
+                messages.error(invalid_json_message(response_body, &e), "Invalid JSON");
             }
-            Ok(json_object) => {
-                if json_object["message"].is_null() {
-                    messages.error("Missing required key 'message'", "Missing key 'message'");
-                } else {
+            Ok(Value::Array(_)) => messages.error(
+                "json endpoint must return an object, not an array",
+                "Invalid response body",
+            ),
+            Ok(json_object) => match json_object.get("message") {
+                None => messages.error("Key 'message' is missing", "Missing key 'message'"),
+                Some(Value::Null) => {
+                    messages.error("Key 'message' is present but null", "Null key 'message'")
+                }
+                Some(message) => {
                     if let Some(map) = json_object.as_object() {
                         for entry in map {
                             if entry.0 != "message" {
@@ -101,41 +154,67 @@ impl Json {
                             }
                         }
                     }
-                    if let Some(str) = json_object["message"].as_str() {
-                        if str != "hello, world!" {
-                            messages.error(
-                                format!("Expected message of 'hello, world!', got '{}'", str),
-                                "Invalid response body",
-                            );
-                        }
-                    } else {
-                        messages.error(
+                    match message.as_str() {
+                        Some(str) if str == self.expected_message => {}
+                        Some(str) => messages.error(
                             format!(
-                                "Expected message of 'hello, world!', got '{}'",
-                                json_object["message"].to_string()
+                                "Expected message of '{}', got '{}'",
+                                self.expected_message, str
                             ),
                             "Invalid response body",
-                        )
+                        ),
+                        None => messages.error(
+                            format!("Key 'message' is not a string: {}", message),
+                            "Invalid response body",
+                        ),
                     }
                 }
-            }
+            },
         };
     }
 }
 
+//
+// PRIVATES
+//
+
+/// Errors when `response_body` holds more than one JSON document, e.g. an
+/// object followed by another; returns whether it holds at most one. Trailing
+/// data which is not JSON is left to parsing to report.
+fn verify_single_json_document(response_body: &str, messages: &mut Messages) -> bool {
+    let documents = Deserializer::from_str(response_body.trim_start_matches(UTF8_BOM))
+        .into_iter::<Value>()
+        .take_while(Result::is_ok)
+        .count();
+    if documents > 1 {
+        messages.error(
+            format!(
+                "Response holds {} concatenated JSON documents; expected exactly one",
+                documents
+            ),
+            "Multiple JSON Documents",
+        );
+    }
+
+    documents <= 1
+}
+
 //
 // TESTS
 //
 
 #[cfg(test)]
 mod tests {
+    use crate::request::{ContentType, Request};
     use crate::test_type::json::Json;
+    use crate::test_type::Executor;
     use crate::verification::Messages;
 
     #[test]
     fn it_should_succeed_on_correct_body() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json("{\"message\":\"Hello, World!\"}", &mut messages);
@@ -147,26 +226,26 @@ mod tests {
     fn it_should_error_on_valid_json_but_bad_message() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
-        let mut messages = Messages::default();
-        json.verify_json("{\"message\":{}}", &mut messages);
-        let mut found = false;
-        for error in messages.errors {
-            if error
-                .message
-                .contains("Expected message of 'hello, world!', got")
-            {
-                found = true;
-                break;
-            }
+        for (body, expected) in [
+            ("{\"message\":{}}", "Key 'message' is not a string: {}"),
+            ("{\"message\":123}", "Key 'message' is not a string: 123"),
+            ("{\"message\":null}", "Key 'message' is present but null"),
+            ("{\"not_message\":null}", "Key 'message' is missing"),
+        ] {
+            let mut messages = Messages::default();
+            json.verify_json(body, &mut messages);
+            assert_eq!(messages.errors.len(), 1, "{}", body);
+            assert_eq!(messages.errors[0].message, expected);
         }
-        assert!(found);
     }
 
     #[test]
     fn it_should_error_on_invalid_json_hello_world_object() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json("{\"message\":", &mut messages);
@@ -179,10 +258,45 @@ mod tests {
             .contains("Invalid JSON"));
     }
 
+    #[test]
+    fn it_should_error_on_concatenated_json_documents() {
+        let json = Json {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
+        };
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\":\"Hello, World!\"}\n", &mut messages);
+        assert!(messages.errors.is_empty());
+
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\":\"Hello, World!\"}\n\n{}", &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert_eq!(
+            messages.errors[0].message,
+            "Response holds 2 concatenated JSON documents; expected exactly one"
+        );
+    }
+
+    #[test]
+    fn it_should_error_on_an_array_body() {
+        let json = Json {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
+        };
+        let mut messages = Messages::default();
+        json.verify_json("[{\"message\":\"Hello, World!\"}]", &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert_eq!(
+            messages.errors[0].message,
+            "json endpoint must return an object, not an array"
+        );
+    }
+
     #[test]
     fn it_should_warn_on_additional_keys() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json(
@@ -203,6 +317,7 @@ mod tests {
     fn it_should_warn_on_additional_bytes() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json(
@@ -226,6 +341,7 @@ mod tests {
     fn it_should_error_on_missing_message_key() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json("{\"not_message\":\"Hello, World!\"}", &mut messages);
@@ -235,13 +351,14 @@ mod tests {
             .get(0)
             .unwrap()
             .message
-            .contains("Missing required key 'message'"));
+            .contains("Key 'message' is missing"));
     }
 
     #[test]
     fn it_should_error_on_invalid_hello_world_value() {
         let json = Json {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
         };
         let mut messages = Messages::default();
         json.verify_json("{\"message\":\"Hello, Moto!\"}", &mut messages);
@@ -251,6 +368,73 @@ mod tests {
             .get(0)
             .unwrap()
             .message
-            .contains("Expected message of 'hello, world!'"));
+            .contains("Expected message of 'Hello, World!'"));
+    }
+
+    #[test]
+    fn it_should_match_the_message_value_case_sensitively() {
+        let json = Json {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            expected_message: "Hello, World!".to_string(),
+        };
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\":\"HELLO, WORLD!\"}", &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+
+        let mut messages = Messages::default();
+        json.verify_json("{\"MESSAGE\":\"Hello, World!\"}", &mut messages);
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_verify_with_the_benchmark_accept_header() {
+        let url = "http://tfb-server:8080/json";
+        let wrk_command = Json {
+            concurrency_levels: vec![16, 32],
+            expected_message: "Hello, World!".to_string(),
+        }
+        .get_wrk_command(url, 15, 16);
+        let (_, accept) = Request::get(url)
+            .accepting(ContentType::Json.accept())
+            .headers()
+            .into_iter()
+            .find(|(name, _)| name == "Accept")
+            .unwrap();
+
+        assert!(wrk_command.contains(&format!("Accept: {}", accept)));
+    }
+
+    #[test]
+    fn it_should_record_additional_bytes() {
+        let json = Json {
+            concurrency_levels: vec![16, 32],
+            expected_message: "Hello, World!".to_string(),
+        };
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\": \"Hello, World!\"}", &mut messages);
+        assert_eq!(messages.extra_bytes, vec![("json".to_string(), 1)]);
+    }
+
+    #[test]
+    fn it_should_verify_against_the_expected_message() {
+        let json = Json {
+            concurrency_levels: vec![16, 32],
+            expected_message: "Hallo, Welt!".to_string(),
+        };
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\":\"Hallo, Welt!\"}", &mut messages);
+        assert!(messages.errors.is_empty());
+        assert!(messages.warnings.is_empty());
+        assert_eq!(
+            json.expected_bodies(),
+            vec!["{\"message\":\"Hallo, Welt!\"}"]
+        );
+
+        let mut messages = Messages::default();
+        json.verify_json("{\"message\":\"Hello, World!\"}", &mut messages);
+        assert_eq!(
+            messages.errors[0].message,
+            "Expected message of 'Hallo, Welt!', got 'Hello, World!'"
+        );
     }
 }
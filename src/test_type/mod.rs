@@ -16,9 +16,14 @@ mod query;
 mod unknown;
 
 use crate::benchmark::BenchmarkCommands;
-use crate::database::Database;
+use crate::config;
+use crate::database::{Database, DatabaseInterface};
 use crate::error::VerifierResult;
-use crate::request::{get_response_headers, ContentType};
+use crate::explain::ExpectedCounts;
+use crate::request::{
+    fetch_with_method, get_response_headers, ContentType, HttpMethod, Request, Response,
+    ResponseHeaders,
+};
 use crate::test_type::fortune::Fortune;
 use crate::test_type::json::Json;
 use crate::test_type::plaintext::Plaintext;
@@ -30,12 +35,32 @@ use crate::test_type::unknown::Unknown;
 use crate::verification::Messages;
 
 use regex::Regex;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::thread::sleep;
 use std::time::Duration;
 use strum_macros::EnumString;
 
+/// Bodies shorter than this are better sent with `Content-Length` than with
+/// chunked `Transfer-Encoding`, whose framing adds bytes to every response.
+const CHUNKED_THRESHOLD_BYTES: usize = 1024;
+
+/// The UTF-8 byte-order mark. `String::from_utf8_lossy` keeps it as this
+/// character, so it is found at the start of a fetched body as it was sent.
+const UTF8_BOM: char = '\u{feff}';
+
+/// Hardening headers which reviewers encourage, though the benchmark does not
+/// require them.
+const SECURITY_HEADERS: [&str; 3] = [
+    "X-Content-Type-Options",
+    "X-Frame-Options",
+    "Content-Security-Policy",
+];
+
+/// Response headers which only serve conditional requests.
+const CONDITIONAL_HEADERS: [&str; 2] = ["ETag", "Last-Modified"];
+
 /// Enumerates all the test types about which this project is aware. In order
 /// to obtain an `Executor` for processing either a verification or a benchmark
 /// of a URL, the test type must be one of these enumerates `TestTypes` *and*
@@ -73,41 +98,68 @@ impl TestType {
         database_name: &Option<String>,
         concurrency_levels: Vec<u32>,
         pipeline_concurrency_levels: Vec<u32>,
+        repetitions: u32,
     ) -> VerifierResult<Box<dyn Executor>> {
         let database = if let Some(name) = database_name {
             Some(Database::get(&name)?)
         } else {
             None
         };
+        Ok(self.get_executor_for(
+            database,
+            concurrency_levels,
+            pipeline_concurrency_levels,
+            repetitions,
+        ))
+    }
+
+    /// Gets an `Executor` for the given `test_type_name` which verifies
+    /// against the given `database`, as `get_executor` does.
+    pub fn get_executor_for(
+        &self,
+        database: Option<Box<dyn DatabaseInterface>>,
+        concurrency_levels: Vec<u32>,
+        pipeline_concurrency_levels: Vec<u32>,
+        repetitions: u32,
+    ) -> Box<dyn Executor> {
         match self {
-            TestType::Json => Ok(Box::new(Json { concurrency_levels })),
-            TestType::SingleQuery => Ok(Box::new(SingleQuery {
-                database_verifier: database.unwrap(),
+            TestType::Json => Box::new(Json {
                 concurrency_levels,
-            })),
-            TestType::MultiQuery => Ok(Box::new(MultiQuery {
+                expected_message: config::expected_json_message(),
+            }),
+            TestType::SingleQuery => Box::new(SingleQuery {
                 database_verifier: database.unwrap(),
                 concurrency_levels,
-            })),
-            TestType::CachedQuery => Ok(Box::new(CachedQuery {
+                repetitions,
+            }),
+            TestType::MultiQuery => Box::new(MultiQuery {
                 database_verifier: database.unwrap(),
                 concurrency_levels,
-            })),
-            TestType::Fortune => Ok(Box::new(Fortune {
+                repetitions,
+            }),
+            TestType::CachedQuery => Box::new(CachedQuery {
+                database_verifier: database,
+                concurrency_levels,
+                repetitions,
+            }),
+            TestType::Fortune => Box::new(Fortune {
                 database_verifier: database.unwrap(),
                 concurrency_levels,
-            })),
-            TestType::Update => Ok(Box::new(Updates {
+                repetitions,
+            }),
+            TestType::Update => Box::new(Updates {
                 database_verifier: database.unwrap(),
                 concurrency_levels,
-            })),
-            TestType::Plaintext => Ok(Box::new(Plaintext {
+                repetitions,
+                update_method: config::update_method(),
+                update_body_template: config::update_body_template(),
+            }),
+            TestType::Plaintext => Box::new(Plaintext {
                 pipeline_concurrency_levels,
-            })),
-            TestType::Unknown(test_type) => Ok(Box::new(Unknown {
-                database_verifier: database.unwrap(),
+            }),
+            TestType::Unknown(test_type) => Box::new(Unknown {
                 test_type: test_type.clone(),
-            })),
+            }),
         }
     }
 }
@@ -120,7 +172,9 @@ impl TestType {
 /// implementation will request said url, capture the response headers and
 /// body, and against them perform a verification or benchmark.
 pub trait Executor {
-    fn wait_for_database_to_be_available(&self);
+    /// Gets the name of the test type this `Executor` verifies, as it is given
+    /// in `TEST_TYPE`; e.g. `db` for `SingleQuery`.
+    fn name(&self) -> &'static str;
 
     /// Gets the `BenchmarkCommands` for the given url.
     ///
@@ -130,19 +184,62 @@ pub trait Executor {
     /// understanding that the caller of this application will consume.
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands>;
 
+    /// Sends a single request to `url` and fails unless it is answered with a
+    /// 200, so that an endpoint which is missing or unreachable is reported
+    /// once rather than by every check in `verify`.
+    fn smoke_test(&self, url: &str) -> VerifierResult<()> {
+        Request::get(url).send().map(|_| ())
+    }
+
     /// Verifies the given `url`.
     fn verify(&self, url: &str) -> VerifierResult<Messages>;
 
+    /// Gets the `ContentType` with which this `Executor` expects `verify` to be
+    /// answered, or `None` if it requests nothing.
+    fn content_type(&self) -> Option<ContentType> {
+        None
+    }
+
+    /// Verifies only the headers of the response from `url`, as `verify` does
+    /// before reading its body; nothing is asked of the database. An
+    /// `Executor` without a `content_type` is verified in full instead.
+    fn verify_headers_only(&self, url: &str) -> VerifierResult<Messages> {
+        let content_type = match self.content_type() {
+            Some(content_type) => content_type,
+            None => return self.verify(url),
+        };
+        let mut messages = Messages::new(url);
+        if let Ok(headers) = get_response_headers(url, content_type.accept(), &mut messages) {
+            messages.headers(&headers);
+            self.verify_headers(&headers, url, content_type, &mut messages);
+        }
+
+        Ok(messages)
+    }
+
+    /// Gets the normalized bodies against which this `Executor` compares the
+    /// responses it verifies, in the order it requests them; empty if its
+    /// responses are not compared against a fixed body.
+    fn expected_bodies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Gets the counts this `Executor` expects to observe in the database
+    /// while verifying, or `None` if it counts nothing there.
+    fn expected_counts(&self) -> Option<ExpectedCounts> {
+        None
+    }
+
     /// Verifies the headers of a framework response
     /// `should_be` is a switch for the acceptable content types
     fn verify_headers(
         &self,
-        headers: &HashMap<String, String>,
+        headers: &ResponseHeaders,
         url: &str,
         should_be: ContentType,
         messages: &mut Messages,
     ) {
-        verify_headers_internal(headers, url, should_be, true, messages)
+        verify_response_headers(headers, url, should_be, messages)
     }
 }
 
@@ -150,15 +247,52 @@ pub trait Executor {
 // PRIVATES
 //
 
+/// Verifies `headers` as the default `Executor::verify_headers` does; free of
+/// any `Executor`, so that it can run on a thread of its own. The HTTP version
+/// is that of the response which sent them.
+fn verify_response_headers(
+    headers: &ResponseHeaders,
+    url: &str,
+    should_be: ContentType,
+    messages: &mut Messages,
+) {
+    verify_headers_internal(
+        headers,
+        headers.http_version.as_deref(),
+        url,
+        should_be,
+        !config::skip_date_retest(),
+        messages,
+    )
+}
+
 fn verify_headers_internal(
     headers: &HashMap<String, String>,
+    http_version: Option<&str>,
     url: &str,
     should_be: ContentType,
     should_retest: bool,
     messages: &mut Messages,
 ) {
-    if !headers.contains_key("Server") && !headers.contains_key("server") {
-        messages.error("Required response header missing: Server", "Missing header");
+    // An HTTP/1.0 response cannot keep its connection alive the way the
+    // benchmark expects.
+    if http_version == Some("HTTP/1.0") {
+        let message = "Response was sent with HTTP/1.0; HTTP/1.1 is expected";
+        if config::require_http_1_1() {
+            messages.error(message, "HTTP/1.0");
+        } else {
+            messages.warning(message, "HTTP/1.0");
+        }
+    }
+    // Some frameworks send the header but intentionally leave out the token,
+    // so a blank value is only warned about.
+    match headers.get("Server").or_else(|| headers.get("server")) {
+        None => messages.error("Required response header missing: Server", "Missing header"),
+        Some(server) if server.trim().is_empty() => messages.warning(
+            "Response header Server is present but empty",
+            "Empty header",
+        ),
+        Some(_) => {}
     }
     if !headers.contains_key("Date") && !headers.contains_key("date") {
         messages.error("Required response header missing: Date", "Missing header");
@@ -184,8 +318,13 @@ fn verify_headers_internal(
         if let Ok(date) = chrono::DateTime::parse_from_rfc2822(date_str) {
             if should_retest {
                 sleep(Duration::from_secs(3));
-                if let Ok(response_headers) = get_response_headers(url, messages) {
-                    if let Some(second_date_str) = response_headers.get("Date") {
+                if let Ok(response_headers) =
+                    get_response_headers(url, should_be.accept(), messages)
+                {
+                    if let Some(second_date_str) = response_headers
+                        .get("Date")
+                        .or_else(|| response_headers.get("date"))
+                    {
                         if let Ok(second_date) =
                             chrono::DateTime::parse_from_rfc2822(second_date_str)
                         {
@@ -194,6 +333,10 @@ fn verify_headers_internal(
                             }
                         }
                     } else {
+                        messages.warning(
+                            "Date header was present on the first request but missing on the retest",
+                            "Inconsistent Date",
+                        );
                     }
                 }
             }
@@ -207,6 +350,9 @@ fn verify_headers_internal(
             );
         }
     }
+    if config::check_security_headers() {
+        verify_security_headers(headers, messages);
+    }
     let mut content_type = headers.get("Content-Type");
     if content_type.is_none() {
         content_type = headers.get("content-type");
@@ -253,27 +399,256 @@ fn verify_headers_internal(
     }
 }
 
+/// Parses `response_body` as JSON with every object key lowercased, so keys
+/// match case-insensitively while values, e.g. the `json` test's `message`,
+/// keep the case the framework sent. A leading byte-order mark is skipped;
+/// `verify_no_byte_order_mark` warns about it.
+fn parse_json_with_lowercase_keys(response_body: &str) -> serde_json::Result<Value> {
+    serde_json::from_str::<Value>(response_body.trim_start_matches(UTF8_BOM)).map(lowercase_keys)
+}
+
+/// Describes why `response_body` is not valid JSON, with the location from
+/// `error` and a snippet of the body around it.
+fn invalid_json_message(response_body: &str, error: &serde_json::Error) -> String {
+    let location = format!(" at line {} column {}", error.line(), error.column());
+    let description = error.to_string();
+    let description = description.trim_end_matches(&location);
+    let line = response_body
+        .trim_start_matches(UTF8_BOM)
+        .lines()
+        .nth(error.line().saturating_sub(1))
+        .unwrap_or_default();
+
+    // `column` counts bytes, so the snippet is widened to character boundaries.
+    let mut start = error.column().saturating_sub(20).min(line.len());
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (error.column() + 20).min(line.len());
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+
+    format!(
+        "Invalid JSON{}: {}; near: `{}`",
+        location,
+        description,
+        &line[start..end]
+    )
+}
+
+/// Lowercases the keys of every object within `value`.
+fn lowercase_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key.to_lowercase(), lowercase_keys(value)))
+                .collect(),
+        ),
+        Value::Array(list) => Value::Array(list.into_iter().map(lowercase_keys).collect()),
+        value => value,
+    }
+}
+
+/// Warns about each conditional-request header (`ETag`, `Last-Modified`) in
+/// `headers`, along with the bytes it adds to every response.
+///
+/// Note: this is only for endpoints whose response never changes (`json`,
+/// `plaintext`), where these headers suggest a caching middleware on the hot
+/// path. It is informational and opt-in; see
+/// `config::warn_on_conditional_headers`.
+fn verify_conditional_headers(headers: &HashMap<String, String>, messages: &mut Messages) {
+    for (name, value) in headers {
+        if CONDITIONAL_HEADERS
+            .iter()
+            .any(|conditional| name.eq_ignore_ascii_case(conditional))
+        {
+            // The header line as sent: `name: value\r\n`.
+            let bytes = name.len() + value.len() + 4;
+            messages.warning(
+                format!(
+                    "Conditional request header \"{}\" found; it adds {} byte(s) to every response of an endpoint which never changes.",
+                    name, bytes
+                ),
+                "Conditional Header",
+            );
+        }
+    }
+}
+
+/// Warns about each of the `SECURITY_HEADERS` missing from `headers`. This is
+/// advisory and never fails verification; see
+/// `config::check_security_headers`.
+fn verify_security_headers(headers: &HashMap<String, String>, messages: &mut Messages) {
+    for security_header in SECURITY_HEADERS.iter() {
+        if !headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case(security_header))
+        {
+            messages.warning(
+                format!(
+                    "Recommended security header missing: {}. This is advisory only.",
+                    security_header
+                ),
+                "Security Header",
+            );
+        }
+    }
+}
+
+/// Warns when a `body_length`-byte body under `CHUNKED_THRESHOLD_BYTES` was
+/// sent with chunked `Transfer-Encoding`. This is advisory; either header
+/// satisfies `verify_headers`.
+fn verify_chunked_encoding(
+    headers: &HashMap<String, String>,
+    body_length: usize,
+    messages: &mut Messages,
+) {
+    let chunked = headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("Transfer-Encoding") && value.to_lowercase().contains("chunked")
+    });
+    if chunked && body_length < CHUNKED_THRESHOLD_BYTES {
+        messages.warning(
+            format!(
+                "Response of {} byte(s) is chunked; consider sending it with \"Content-Length\" instead.",
+                body_length
+            ),
+            "Chunked Response",
+        );
+    }
+}
+
+/// Sends a HEAD request for `url` and warns unless it is answered as the GET
+/// was, with `get_headers`. Opt-in; see `config::check_head`.
+///
+/// Note: `curl` never reads the body of a response to a HEAD request, so one
+/// sent regardless cannot be detected here.
+fn verify_head(
+    url: &str,
+    accept: &'static str,
+    get_headers: &HashMap<String, String>,
+    messages: &mut Messages,
+) {
+    let headers = Request::get(url).accepting(accept).headers();
+    let get_content_type = get_headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+        .map(|(_, value)| value.as_str());
+    report_head(
+        fetch_with_method(url, HttpMethod::Head, None, &headers),
+        get_content_type,
+        messages,
+    );
+}
+
+/// Warns unless the `head` response is a 200 with the same `Content-Type` as
+/// the GET, `get_content_type`.
+fn report_head(
+    head: VerifierResult<Response>,
+    get_content_type: Option<&str>,
+    messages: &mut Messages,
+) {
+    match head {
+        Err(e) => messages.warning(
+            format!("HEAD request was not answered with a 200: {}", e),
+            "HEAD Unsupported",
+        ),
+        Ok(response) if response.content_type.as_deref() != get_content_type => messages.warning(
+            format!(
+                "Content-Type of the response to a HEAD request, {:?}, differs from that to a GET, {:?}.",
+                response.content_type.as_deref().unwrap_or_default(),
+                get_content_type.unwrap_or_default()
+            ),
+            "HEAD Content-Type",
+        ),
+        Ok(_) => {}
+    }
+}
+
+/// Records the bytes by which the `body_length`-byte response to `check`
+/// exceeds the `baseline` (its minimum) size, and warns when that is more
+/// than `config::max_oversize_percent` of the baseline; e.g. a page padded
+/// with whitespace.
+fn verify_response_size(check: &str, body_length: usize, baseline: usize, messages: &mut Messages) {
+    let extra_bytes = body_length.saturating_sub(baseline);
+    messages.extra_bytes(check, extra_bytes);
+    let max_percent = config::max_oversize_percent();
+    if extra_bytes as f64 > baseline as f64 * max_percent / 100.0 {
+        messages.warning(
+            format!(
+                "Response of {} byte(s) is {} byte(s) over its minimum of {}, more than {}% over. Consider removing unnecessary whitespace.",
+                body_length, extra_bytes, baseline, max_percent
+            ),
+            "Oversized Response",
+        );
+    }
+}
+
+/// Warns when `response_body` begins with a UTF-8 byte-order mark, which
+/// JSON must not carry (RFC 8259) and which strict parsers reject.
+fn verify_no_byte_order_mark(response_body: &str, messages: &mut Messages) {
+    if response_body.starts_with(UTF8_BOM) {
+        messages.warning(
+            "Response body begins with a UTF-8 byte-order mark; JSON must not include one.",
+            "UTF-8 BOM",
+        );
+    }
+}
+
 //
 // TESTS
 //
 
 #[cfg(test)]
 mod tests {
-    use crate::request::ContentType;
-    use crate::test_type::{verify_headers_internal, TestType};
+    use crate::error::VerifierError::Non200Response;
+    use crate::request::{ContentType, Response, ResponseHeaders};
+    use crate::test_type::{
+        invalid_json_message, parse_json_with_lowercase_keys, report_head, verify_chunked_encoding,
+        verify_conditional_headers, verify_headers_internal, verify_no_byte_order_mark,
+        verify_response_headers, verify_response_size, verify_security_headers, TestType,
+    };
     use crate::verification::Messages;
+    use serde_json::Value;
     use std::collections::HashMap;
+    use std::time::Duration;
 
     //
     // verify_headers
     //
 
+    #[test]
+    fn it_should_take_the_http_version_from_the_verified_response() {
+        let headers = ResponseHeaders {
+            headers: HashMap::from([
+                ("Server".to_string(), "tfb".to_string()),
+                ("Content-Type".to_string(), "application/json".to_string()),
+                ("Content-Length".to_string(), "27".to_string()),
+            ]),
+            http_version: Some("HTTP/1.0".to_string()),
+        };
+        let mut messages = Messages::default();
+        // No request is made; there is no `Date` to retest.
+        verify_response_headers(
+            &headers,
+            "http://tfb-server:1",
+            ContentType::Json,
+            &mut messages,
+        );
+        assert_eq!(messages.warnings.len(), 1);
+        assert_eq!(
+            messages.warnings[0].message,
+            "Response was sent with HTTP/1.0; HTTP/1.1 is expected"
+        );
+    }
+
     #[test]
     fn it_should_error_on_missing_headers() {
         let map = HashMap::new();
         let mut messages = Messages::default();
         verify_headers_internal(
             &map,
+            None,
             "http://google.com",
             ContentType::Json,
             false,
@@ -315,6 +690,204 @@ mod tests {
         assert!(transfer);
     }
 
+    fn server_messages(server: &str) -> Messages {
+        let mut map = HashMap::new();
+        map.insert("Server".to_string(), server.to_string());
+        let mut messages = Messages::default();
+        verify_headers_internal(
+            &map,
+            None,
+            "http://google.com",
+            ContentType::Json,
+            false,
+            &mut messages,
+        );
+        messages
+    }
+
+    #[test]
+    fn it_should_warn_on_empty_server_header() {
+        let messages = server_messages(" ");
+        assert!(messages
+            .warnings
+            .iter()
+            .any(|warning| warning.message == "Response header Server is present but empty"));
+        assert!(!messages
+            .errors
+            .iter()
+            .any(|error| error.message.contains("Server")));
+    }
+
+    #[test]
+    fn it_should_accept_server_header_with_value() {
+        let messages = server_messages("tfb");
+        assert!(!messages
+            .warnings
+            .iter()
+            .any(|warning| warning.message.contains("Server")));
+        assert!(!messages
+            .errors
+            .iter()
+            .any(|error| error.message.contains("Server")));
+    }
+
+    #[test]
+    fn it_should_warn_on_http_1_0_response() {
+        let map = HashMap::new();
+        let mut messages = Messages::default();
+        verify_headers_internal(
+            &map,
+            Some("HTTP/1.0"),
+            "http://google.com",
+            ContentType::Json,
+            false,
+            &mut messages,
+        );
+        assert!(messages
+            .warnings
+            .iter()
+            .any(|warning| warning.message.contains("HTTP/1.0")));
+
+        let mut messages = Messages::default();
+        verify_headers_internal(
+            &map,
+            Some("HTTP/1.1"),
+            "http://google.com",
+            ContentType::Json,
+            false,
+            &mut messages,
+        );
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_lowercase_keys_but_not_values() {
+        let json = parse_json_with_lowercase_keys(
+            "[{\"MESSAGE\":\"Hello, World!\",\"Inner\":{\"ID\":1}}]",
+        )
+        .unwrap();
+        assert_eq!(json[0]["message"], "Hello, World!");
+        assert_eq!(json[0]["inner"]["id"], 1);
+    }
+
+    #[test]
+    fn it_should_locate_invalid_json() {
+        let body = "{\"id\":1,\"randomnumber\":2,}";
+        let error = serde_json::from_str::<Value>(body).unwrap_err();
+        assert_eq!(
+            invalid_json_message(body, &error),
+            "Invalid JSON at line 1 column 26: trailing comma; near: `1,\"randomnumber\":2,}`"
+        );
+    }
+
+    #[test]
+    fn it_should_locate_invalid_json_without_splitting_characters() {
+        let body = "[\"フレームワークのベンチマーク\"";
+        let error = serde_json::from_str::<Value>(body).unwrap_err();
+        assert!(invalid_json_message(body, &error).contains("ベンチマーク"));
+    }
+
+    #[test]
+    fn it_should_warn_on_chunked_tiny_responses() {
+        let mut map = HashMap::new();
+        map.insert("transfer-encoding".to_string(), "chunked".to_string());
+        let mut messages = Messages::default();
+        verify_chunked_encoding(&map, 13, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+
+        verify_chunked_encoding(&map, 4096, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+
+        map.clear();
+        map.insert("Content-Length".to_string(), "13".to_string());
+        verify_chunked_encoding(&map, 13, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+    }
+
+    #[test]
+    fn it_should_warn_on_conditional_headers() {
+        let mut map = HashMap::new();
+        map.insert("etag".to_string(), "\"abc\"".to_string());
+        map.insert(
+            "Last-Modified".to_string(),
+            "Wed, 21 Oct 2015 07:28:00 GMT".to_string(),
+        );
+        let mut messages = Messages::default();
+        verify_conditional_headers(&map, &mut messages);
+
+        assert_eq!(messages.warnings.len(), 2);
+        assert!(messages.warnings.iter().any(|warning| warning
+            .message
+            .contains("\"etag\" found; it adds 13 byte(s)")));
+    }
+
+    #[test]
+    fn it_should_not_warn_without_conditional_headers() {
+        let mut map = HashMap::new();
+        map.insert("Server".to_string(), "tfb".to_string());
+        map.insert("Content-Length".to_string(), "27".to_string());
+        let mut messages = Messages::default();
+        verify_conditional_headers(&map, &mut messages);
+
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_warn_about_missing_security_headers() {
+        let mut map = HashMap::new();
+        map.insert("x-content-type-options".to_string(), "nosniff".to_string());
+        map.insert("X-Frame-Options".to_string(), "DENY".to_string());
+        let mut messages = Messages::default();
+        verify_security_headers(&map, &mut messages);
+
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .starts_with("Recommended security header missing: Content-Security-Policy"));
+    }
+
+    #[test]
+    fn it_should_warn_about_responses_well_over_their_baseline() {
+        let mut messages = Messages::default();
+        verify_response_size("fortune", 1_100, 1_000, &mut messages);
+        assert!(messages.warnings.is_empty());
+        assert_eq!(messages.extra_bytes, vec![("fortune".to_string(), 100)]);
+
+        let mut messages = Messages::default();
+        verify_response_size("fortune", 5_000, 1_000, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .starts_with("Response of 5000 byte(s) is 4000 byte(s) over its minimum of 1000"));
+    }
+
+    #[test]
+    fn it_should_warn_about_head_requests_unlike_gets() {
+        let head = |content_type: &str| Response {
+            body: Vec::new(),
+            time_to_first_byte: Duration::from_millis(1),
+            total_time: Duration::from_millis(1),
+            content_type: Some(content_type.to_string()),
+        };
+
+        let mut messages = Messages::default();
+        report_head(Ok(head("text/plain")), Some("text/plain"), &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let mut messages = Messages::default();
+        report_head(Ok(head("text/html")), Some("text/plain"), &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+
+        let mut messages = Messages::default();
+        let url = "http://tfb-server:8080/plaintext";
+        let refused = Non200Response(url.to_string(), 405, String::new());
+        report_head(Err(refused), Some("text/plain"), &mut messages);
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0].message.contains("405"));
+    }
+
     //
     // verify test types
     //
@@ -360,4 +933,19 @@ mod tests {
             panic!("plaintext test type broken");
         }
     }
+
+    #[test]
+    fn it_should_warn_about_a_byte_order_mark() {
+        let mut messages = Messages::default();
+        verify_no_byte_order_mark(r#"{"message":"Hello, World!"}"#, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let with_bom = String::from_utf8_lossy(b"\xEF\xBB\xBF{\"message\":\"Hello, World!\"}");
+        verify_no_byte_order_mark(&with_bom, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0].message.contains("byte-order mark"));
+
+        let json = parse_json_with_lowercase_keys(&with_bom).unwrap();
+        assert_eq!(json["message"], "Hello, World!");
+    }
 }
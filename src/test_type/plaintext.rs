@@ -1,16 +1,27 @@
 use crate::benchmark::BenchmarkCommands;
+use crate::config;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::Executor;
+use crate::logger::{log, LogLevel, LogOptions};
+use crate::request::{
+    fetch_pipelined, get_compression, get_response_body, get_response_headers,
+    verify_connection_reuse, Compression, ContentType,
+};
+use crate::test_type::{
+    verify_chunked_encoding, verify_conditional_headers, verify_head, Executor,
+};
 use crate::verification::Messages;
+use colored::Colorize;
 use std::cmp::min;
 
+/// The number of requests the benchmark pipelines on each connection.
+const PIPELINE_DEPTH: usize = 16;
+
 pub struct Plaintext {
     pub pipeline_concurrency_levels: Vec<u32>,
 }
 impl Executor for Plaintext {
-    fn wait_for_database_to_be_available(&self) {
-        // No database; intentionally blank.
+    fn name(&self) -> &'static str {
+        "plaintext"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -32,10 +43,20 @@ impl Executor for Plaintext {
         })
     }
 
+    fn expected_bodies(&self) -> Vec<String> {
+        vec!["Hello, World!".to_string()]
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Plaintext)
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
 
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Plaintext.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
             self.verify_headers(
                 &response_headers,
@@ -43,10 +64,34 @@ impl Executor for Plaintext {
                 ContentType::Plaintext,
                 &mut messages,
             );
-            if let Some(response_body) = get_response_body(&url, &mut messages) {
+            verify_connection_reuse(url, &mut messages);
+            if config::warn_on_conditional_headers() {
+                verify_conditional_headers(&response_headers, &mut messages);
+            }
+            if config::check_head() {
+                verify_head(
+                    url,
+                    ContentType::Plaintext.accept(),
+                    &response_headers,
+                    &mut messages,
+                );
+            }
+            if let Some(response_body) =
+                get_response_body(&url, ContentType::Plaintext.accept(), &mut messages)
+            {
                 messages.body(&response_body);
 
+                let errors = messages.errors.len();
                 self.verify_plaintext(&response_body, &mut messages);
+                verify_chunked_encoding(&response_headers, response_body.len(), &mut messages);
+                // Pipelining is only worth checking once a single request is
+                // answered correctly.
+                if messages.errors.len() == errors {
+                    self.verify_pipelining(url, &mut messages);
+                }
+                if config::check_compression() {
+                    verify_uncompressed(url, &mut messages);
+                }
             }
         }
 
@@ -57,11 +102,17 @@ impl Plaintext {
     fn verify_plaintext(&self, response_body: &str, messages: &mut Messages) {
         let body = response_body.to_lowercase();
         let expected = "hello, world!";
-        let extra_bytes = body.len() - expected.len();
+        // Measured against the body as sent; lowercasing may change its length.
+        let extra_bytes = response_body.len().saturating_sub(expected.len());
+        messages.extra_bytes("plaintext", extra_bytes);
 
-        if !body.contains(expected) {
+        // Only surrounding whitespace is tolerated, and warned about below.
+        if body.trim() != expected {
             messages.error(
-                format!("Could not find 'Hello, World!' in response: '{}'", body),
+                format!(
+                    "Response is not exactly 'Hello, World!': '{}'",
+                    response_body
+                ),
                 "Invalid response body",
             );
         }
@@ -74,13 +125,58 @@ impl Plaintext {
         }
     }
 
+    /// Verifies that `PIPELINE_DEPTH` requests pipelined on one connection, as
+    /// the benchmark sends them, are each answered correctly and in order.
+    fn verify_pipelining(&self, url: &str, messages: &mut Messages) {
+        match fetch_pipelined(
+            url,
+            &ContentType::Plaintext.benchmark_accept(),
+            PIPELINE_DEPTH,
+        ) {
+            Ok(Some(responses)) => self.verify_pipelined_responses(&responses, messages),
+            Ok(None) => log(
+                format!("Pipelining was not verified; {} is not plain HTTP", url).yellow(),
+                LogOptions {
+                    border: None,
+                    border_bottom: None,
+                    level: LogLevel::Info,
+                },
+            ),
+            Err(e) => messages.error(
+                format!("{} pipelined requests failed: {}", PIPELINE_DEPTH, e),
+                "Pipelining Failed",
+            ),
+        }
+    }
+
+    /// Reports the first of the pipelined `responses` which is not a `200`
+    /// with the plaintext body.
+    fn verify_pipelined_responses(&self, responses: &[(u32, String)], messages: &mut Messages) {
+        let malformed = responses
+            .iter()
+            .position(|(code, body)| *code != 200 || body.trim().to_lowercase() != "hello, world!");
+        if let Some(index) = malformed {
+            let (code, body) = &responses[index];
+            messages.error(
+                format!(
+                    "Pipelined response {} of {} was {} with body '{}'; expected 200 with 'Hello, World!'",
+                    index + 1,
+                    responses.len(),
+                    code,
+                    body
+                ),
+                "Pipelining Failed",
+            );
+        }
+    }
+
     fn get_wrk_command(&self, url: &str, duration: u32, concurrency: u32) -> Vec<String> {
         vec![
             "wrk",
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: text/plain,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Plaintext.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -96,20 +192,82 @@ impl Plaintext {
             "-s",
             "pipeline.lua",
             "--",
-            "16",
-        ].iter().map(|item| item.to_string()).collect()
+            &format!("{}", PIPELINE_DEPTH),
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
     }
 }
 
+//
+// PRIVATES
+//
+
+/// Requests `url` offering to accept gzip, and warns if the server compressed
+/// the response; see `report_compression`.
+fn verify_uncompressed(url: &str, messages: &mut Messages) {
+    if let Ok(compression) = get_compression(url, ContentType::Plaintext.accept()) {
+        report_compression(&compression, messages);
+    }
+}
+
+/// Warns when the plaintext response was compressed at all. Its body is so
+/// small that the encoding's framing outweighs any saving, and compressing
+/// every response costs the server throughput in the benchmark.
+fn report_compression(compression: &Compression, messages: &mut Messages) {
+    let encoding = match compression.encoding.as_deref() {
+        Some(encoding) if !encoding.eq_ignore_ascii_case("identity") => encoding,
+        _ => return,
+    };
+    messages.warning(
+        format!(
+            "Response was compressed ({}) to {} bytes from {} bytes; consider disabling compression for plaintext.",
+            encoding, compression.transferred_bytes, compression.decompressed_bytes
+        ),
+        "Harmful Compression",
+    );
+}
+
 //
 // TESTS
 //
 
 #[cfg(test)]
 mod tests {
-    use crate::test_type::plaintext::Plaintext;
+    use crate::request::{Compression, ContentType, Request};
+    use crate::test_type::plaintext::{report_compression, Plaintext};
     use crate::verification::Messages;
 
+    #[test]
+    fn it_should_warn_when_the_response_is_compressed() {
+        let mut messages = Messages::default();
+        report_compression(
+            &Compression {
+                encoding: Some("gzip".to_string()),
+                transferred_bytes: 33,
+                decompressed_bytes: 13,
+            },
+            &mut messages,
+        );
+        assert_eq!(messages.warnings.len(), 1);
+        assert_eq!(
+            messages.warnings[0].message,
+            "Response was compressed (gzip) to 33 bytes from 13 bytes; consider disabling compression for plaintext."
+        );
+
+        let mut messages = Messages::default();
+        report_compression(
+            &Compression {
+                encoding: None,
+                transferred_bytes: 13,
+                decompressed_bytes: 13,
+            },
+            &mut messages,
+        );
+        assert!(messages.warnings.is_empty());
+    }
+
     #[test]
     fn it_should_succeed_on_correct_body() {
         let plaintext = Plaintext {
@@ -132,7 +290,7 @@ mod tests {
         for error in messages.errors {
             if error
                 .message
-                .contains("Could not find 'Hello, World!' in response")
+                .contains("Response is not exactly 'Hello, World!'")
             {
                 found = true;
                 break;
@@ -140,4 +298,99 @@ mod tests {
         }
         assert!(found);
     }
+
+    #[test]
+    fn it_should_fail_without_panic_on_empty_body() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        let mut messages = Messages::default();
+        plaintext.verify_plaintext("", &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_fail_without_panic_on_short_body() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        let mut messages = Messages::default();
+        plaintext.verify_plaintext("hi", &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_fail_on_body_merely_containing_message() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        for body in ["Hello, World! Hello, World!", "xHello, World!y"] {
+            let mut messages = Messages::default();
+            plaintext.verify_plaintext(body, &mut messages);
+            assert_eq!(messages.errors.len(), 1, "{}", body);
+        }
+    }
+
+    #[test]
+    fn it_should_only_warn_on_surrounding_whitespace() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        let mut messages = Messages::default();
+        plaintext.verify_plaintext("hello, world!\n", &mut messages);
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+    }
+
+    #[test]
+    fn it_should_verify_with_the_benchmark_accept_header() {
+        let url = "http://tfb-server:8080/plaintext";
+        let wrk_command = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        }
+        .get_wrk_command(url, 15, 16);
+        let (_, accept) = Request::get(url)
+            .accepting(ContentType::Plaintext.accept())
+            .headers()
+            .into_iter()
+            .find(|(name, _)| name == "Accept")
+            .unwrap();
+
+        assert!(wrk_command.contains(&format!("Accept: {}", accept)));
+    }
+
+    #[test]
+    fn it_should_record_additional_bytes() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        let mut messages = Messages::default();
+        plaintext.verify_plaintext("Hello, World!\n\n", &mut messages);
+        assert_eq!(messages.extra_bytes, vec![("plaintext".to_string(), 2)]);
+        assert_eq!(
+            messages.extra_bytes_summary().unwrap(),
+            "Bytes over minimum: plaintext +2 (total +2)"
+        );
+    }
+
+    #[test]
+    fn it_should_report_the_first_malformed_pipelined_response() {
+        let plaintext = Plaintext {
+            pipeline_concurrency_levels: vec![256, 1024, 4096, 16384],
+        };
+        let mut responses = vec![(200, "Hello, World!".to_string()); 16];
+        let mut messages = Messages::default();
+        plaintext.verify_pipelined_responses(&responses, &mut messages);
+        assert!(messages.errors.is_empty());
+
+        responses[3] = (400, "Bad Request".to_string());
+        responses[7] = (200, "".to_string());
+        plaintext.verify_pipelined_responses(&responses, &mut messages);
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .starts_with("Pipelined response 4 of 16 was 400 with body 'Bad Request'"));
+    }
 }
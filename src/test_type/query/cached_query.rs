@@ -1,20 +1,26 @@
 use crate::benchmark::BenchmarkCommands;
 use crate::database::DatabaseInterface;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::query::Query;
+use crate::request::{
+    get_response_body, get_response_headers, verify_connection_reuse, ContentType, Request,
+};
+use crate::test_type::query::{Query, MAX_QUERIES, MIN_QUERIES};
 use crate::test_type::Executor;
 use crate::verification::Messages;
 use std::cmp::min;
 
 pub struct CachedQuery {
+    /// Only used to check that the cache is being used; that check is skipped
+    /// without a database.
+    pub database_verifier: Option<Box<dyn DatabaseInterface>>,
     pub concurrency_levels: Vec<u32>,
-    pub database_verifier: Box<dyn DatabaseInterface>,
+    /// The times each burst of requests is sent while counting queries.
+    pub repetitions: u32,
 }
 impl Query for CachedQuery {}
 impl Executor for CachedQuery {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "cached_query"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -33,33 +39,44 @@ impl Executor for CachedQuery {
         })
     }
 
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Json)
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
-
-        // Initialization for query counting
-        let repetitions = 2;
-        let concurrency = *self.concurrency_levels.iter().max().unwrap();
-        let expected_queries = 20 * repetitions * concurrency;
-        let _expected_rows = expected_queries;
+        if let Some(database_verifier) = &self.database_verifier {
+            database_verifier.verify_schema(&mut messages);
+        }
 
         // Because this test type is going to make a LOT of requests with a reasonably long timeout,
         // we use `get_response_headers` as a sentinel. If a `CurlError` is thrown, then we do not
         // perform any of the follow-up requests to conserve time.
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Json.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
             self.verify_headers(&response_headers, &url, ContentType::Json, &mut messages);
+            verify_connection_reuse(url, &mut messages);
+            self.verify_count_url(url, &mut messages);
 
             let test_cases = ["2", "0", "foo", "501", ""];
-            let min = 1;
-            let max = 500;
+            let min = MIN_QUERIES;
+            let max = MAX_QUERIES;
 
             for test_case in test_cases.iter() {
                 let expected_length = self.translate_query_count(*test_case, min, max);
-                let url = format!("{}{}", url, test_case);
+                let count_url = format!("{}{}", url, test_case);
 
-                if let Some(response_body) = get_response_body(&url, &mut messages) {
+                if let Some(response_body) =
+                    get_response_body(&count_url, ContentType::Json.accept(), &mut messages)
+                {
                     messages.body(&response_body);
                     self.verify_with_length(&response_body, expected_length, &mut messages);
+
+                    if expected_length == max {
+                        self.verify_caching(url, &response_body, &mut messages);
+                    }
                 }
             }
         }
@@ -68,13 +85,36 @@ impl Executor for CachedQuery {
     }
 }
 impl CachedQuery {
+    /// Verifies that the largest response was answered from a cache which
+    /// holds more than a single row, and, given a database, that requests are
+    /// answered without querying it.
+    fn verify_caching(&self, url: &str, response_body: &str, messages: &mut Messages) {
+        // A broken cache may answer every query with the same row.
+        self.verify_distinct_ids(response_body, messages);
+
+        if let Some(database_verifier) = &self.database_verifier {
+            let repetitions = self.repetitions;
+            let concurrency = *self.concurrency_levels.iter().max().unwrap();
+            // The queries which would be executed without a cache.
+            let uncached_queries = 20 * repetitions * concurrency;
+            database_verifier.verify_queries_are_cached(
+                &Request::get(&format!("{}20", url)),
+                "world",
+                concurrency,
+                repetitions,
+                uncached_queries,
+                messages,
+            );
+        }
+    }
+
     fn get_wrk_command(&self, url: &str, duration: u32, concurrency: u32) -> Vec<String> {
         vec![
             "wrk",
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Json.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -87,6 +127,42 @@ impl CachedQuery {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::test_type::query::cached_query::CachedQuery;
+    use crate::verification::Messages;
+
+    #[test]
+    fn it_should_warn_when_the_cache_repeats_one_row() {
+        let cached_query = CachedQuery {
+            database_verifier: None,
+            concurrency_levels: vec![16],
+            repetitions: 1,
+        };
+        let mut messages = Messages::default();
+        let row = "{\"id\":1234,\"randomNumber\":4321}";
+        let body = format!("[{}]", vec![row; 500].join(","));
+        cached_query.verify_caching(
+            "http://tfb-server:8080/cached-queries?count=",
+            &body,
+            &mut messages,
+        );
+
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("Only 1 distinct id(s) found in 500 returned objects"));
     }
 }
@@ -3,10 +3,183 @@ pub(crate) mod multi_query;
 pub(crate) mod single_query;
 pub(crate) mod updates;
 
+use crate::config;
+use crate::error::VerifierError::Non200Response;
+use crate::error::VerifierResult;
+use crate::request::{verify_declared_charset, ContentType, Request, Response};
+use crate::test_type::{
+    invalid_json_message, parse_json_with_lowercase_keys, verify_no_byte_order_mark,
+};
 use crate::verification::Messages;
 use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
+/// The `queries` parameter is clamped to between these counts inclusive;
+/// anything which is not an integer counts as `MIN_QUERIES`.
+const MIN_QUERIES: i32 = 1;
+const MAX_QUERIES: i32 = 500;
+
+/// A declarative description of the JSON object expected from a query test
+/// type; see `world_object_schema`.
+///
+/// Every field is required, keys are matched case-insensitively, and any key
+/// which is not one of the `fields` is warned about.
+pub struct ObjectSchema {
+    pub fields: Vec<IntegerField>,
+}
+
+/// An integer-valued field of an `ObjectSchema`.
+pub struct IntegerField {
+    /// The lowercase name of the key.
+    pub key: &'static str,
+    /// Whether an int-string (e.g. `"123"`) is accepted with a warning rather
+    /// than rejected.
+    pub allow_int_string: bool,
+    pub min: Option<Bound>,
+    pub max: Option<Bound>,
+}
+
+/// An inclusive limit on the value of an `IntegerField` and how a value
+/// beyond it is reported. In `message`, the placeholders `{value}`, `{min}`,
+/// and `{max}` are replaced with the offending value and the field's limits.
+pub struct Bound {
+    pub limit: i64,
+    pub severity: Severity,
+    pub message: &'static str,
+    pub short_message: &'static str,
+}
+
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The schema of a `world` row; e.g. `{"id": 2354,"randomNumber":8952}`.
+///
+/// The `rows` `id`s run from `id_base` (see `config::world_id_base` and
+/// `config::world_row_count`), and the `randomNumber`s from 1 to
+/// `max_random_number`.
+pub fn world_object_schema(id_base: i64, rows: i64, max_random_number: i64) -> ObjectSchema {
+    ObjectSchema {
+        fields: vec![
+            IntegerField {
+                key: "id",
+                allow_int_string: true,
+                min: Some(Bound {
+                    limit: id_base,
+                    severity: Severity::Warning,
+                    message: "Response key 'id' should be between {min} and {max}: {value}",
+                    short_message: "Value Out of Range",
+                }),
+                max: Some(Bound {
+                    limit: id_base + rows - 1,
+                    severity: Severity::Warning,
+                    message: "Response key 'id' should be between {min} and {max}: {value}",
+                    short_message: "Value Out of Range",
+                }),
+            },
+            IntegerField {
+                key: "randomnumber",
+                allow_int_string: false,
+                min: Some(Bound {
+                    limit: 1,
+                    severity: Severity::Error,
+                    message: "Response key 'randomnumber' must be greater than zero: {value}",
+                    short_message: "Invalid Value",
+                }),
+                max: Some(Bound {
+                    limit: max_random_number,
+                    severity: Severity::Warning,
+                    message: "Response key `randomNumber` is over {max}. This may negatively affect performance by sending extra bytes.",
+                    short_message: "Value Out of Range",
+                }),
+            },
+        ],
+    }
+}
+
+/// Validates `json` against `schema`, reporting each violation to `messages`.
+///
+/// A missing key is reported on its own since there is little sense in
+/// validating the rest of an object which is the wrong shape.
+pub fn verify_object_schema(
+    json: &Map<String, Value>,
+    schema: &ObjectSchema,
+    messages: &mut Messages,
+) {
+    let mut found_keys = HashMap::new();
+    let mut unknown_keys = Vec::new();
+    for key in json.keys() {
+        let lowercase_key = key.to_lowercase();
+        if let Some(field) = schema
+            .fields
+            .iter()
+            .find(|field| field.key == lowercase_key)
+        {
+            found_keys.insert(field.key, key);
+        } else {
+            unknown_keys.push(lowercase_key);
+        }
+    }
+    if let Some(missing) = schema
+        .fields
+        .iter()
+        .find(|field| !found_keys.contains_key(field.key))
+    {
+        messages.error(
+            format!("Response object was missing required key: {}", missing.key),
+            "Missing Key",
+        );
+        return;
+    }
+    match unknown_keys.len() {
+        0 => {}
+        1 => messages.warning(
+            format!(
+                "An extra key is being included with the db object: {}",
+                unknown_keys[0]
+            ),
+            "Extra Key",
+        ),
+        _ => messages.warning(
+            format!(
+                "Extra keys are being included with the db object: {}",
+                unknown_keys.join(", ")
+            ),
+            "Extra Keys",
+        ),
+    }
+    for field in &schema.fields {
+        verify_integer_field(&json[found_keys[field.key]], field, messages);
+    }
+}
+
+/// How the `queries` parameter is given; each of these should be treated as a
+/// request for a single object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueriesCase {
+    /// No `queries` parameter at all.
+    Absent,
+    /// `queries=`.
+    Empty,
+    /// `queries=1`.
+    One,
+}
+impl QueriesCase {
+    /// Gets the url for this case from `url`, which ends with `queries=`.
+    pub fn url(&self, url: &str) -> String {
+        match self {
+            QueriesCase::Absent => match url.rfind(['?', '&']) {
+                Some(index) => url[..index].to_string(),
+                None => url.to_string(),
+            },
+            QueriesCase::Empty => url.to_string(),
+            QueriesCase::One => format!("{}1", url),
+        }
+    }
+}
+
 pub trait Query {
     /// Ensures that `json` is a JSON object with keys 'id' and 'randomNumber'
     /// that both map to ints.
@@ -15,108 +188,15 @@ pub trait Query {
     ///
     /// `{"id": 2354,"randomNumber":8952}`
     fn verify_random_number_object(&self, json: &Map<String, Value>, messages: &mut Messages) {
-        let mut id_found = false;
-        let mut id_key = "id";
-        let mut random_number_found = false;
-        let mut random_number_key = "randomnumber";
-        let mut keys = 0;
-        let mut unknown_keys = String::new();
-        for key in json.keys() {
-            keys += 1;
-            if key.to_lowercase() == "id" {
-                id_found = true;
-                id_key = key;
-            } else if key.to_lowercase() == "randomnumber" {
-                random_number_found = true;
-                random_number_key = key;
-            } else {
-                unknown_keys.push_str(&format!("{}, ", key.to_lowercase()));
-            }
-        }
-        if !id_found {
-            messages.error(
-                "Response object was missing required key: id",
-                "Missing Key",
-            );
-        } else if !random_number_found {
-            messages.error(
-                "Response object was missing required key: randomnumber",
-                "Missing Key",
-            );
-        } else {
-            if keys > 2 {
-                // Always ends with ", "
-                unknown_keys.pop();
-                unknown_keys.pop();
-                let single = format!(
-                    "An extra key is being included with the db object: {}",
-                    unknown_keys
-                );
-                let plural = format!(
-                    "Extra keys are being included with the db object: {}",
-                    unknown_keys
-                );
-                let (warning, short) = match keys {
-                    3 => (single, "Extra Key"),
-                    _ => (plural, "Extra Keys"),
-                };
-                messages.warning(warning, short);
-            }
-            let id = {
-                let mut tmp_id = json[id_key].as_i64();
-                if let Some(id_str) = json[id_key].as_str() {
-                    if let Ok(parsed_id) = i64::from_str(id_str) {
-                        messages.warning(
-                            format!("Response key 'id' is int-string; should be int: {}. This may negatively affect performance by sending extra bytes.", id_str),
-                            "Extra Bytes"
-                        );
-                        tmp_id = Some(parsed_id);
-                    }
-                }
-                if tmp_id.is_none() {
-                    messages.error(
-                        format!(
-                            "Response key 'id' does not map to an integer: {}",
-                            json[id_key]
-                        ),
-                        "Invalid Value",
-                    );
-                }
-                tmp_id.unwrap_or(0)
-            };
-
-            if id > 10_000 {
-                messages.warning(
-                    format!("Response key 'id' should be between 1 and 10,000: {}", id),
-                    "Value Out of Range",
-                );
-            }
-
-            if let Some(random_number) = json[random_number_key].as_i64() {
-                if random_number < 1 {
-                    messages.error(
-                        format!(
-                            "Response key 'randomnumber' must be greater than zero: {}",
-                            random_number
-                        ),
-                        "Invalid Value",
-                    );
-                } else if random_number > 10_000 {
-                    messages.warning(
-                        "Response key `randomNumber` is over 10,000. This may negatively affect performance by sending extra bytes.",
-                        "Value Out of Range"
-                    );
-                }
-            } else {
-                messages.error(
-                    format!(
-                        "Response key 'randomnumber' does not map to an integer: {}",
-                        json[random_number_key]
-                    ),
-                    "Invalid Value",
-                );
-            }
-        }
+        verify_object_schema(
+            json,
+            &world_object_schema(
+                config::world_id_base(),
+                config::world_row_count(),
+                config::max_random_number(),
+            ),
+            messages,
+        );
     }
 
     /// Verifies the given `response_body` and `expected_count`.
@@ -126,41 +206,170 @@ pub trait Query {
         expected_count: i32,
         messages: &mut Messages,
     ) {
-        match serde_json::from_str::<Value>(&response_body.to_lowercase()) {
+        verify_no_byte_order_mark(response_body, messages);
+        match parse_json_with_lowercase_keys(response_body) {
             Err(e) => {
-                messages.error(format!("Invalid JSON: {:?}", e), "Invalid JSON");
+                messages.error(invalid_json_message(response_body, &e), "Invalid JSON");
             }
             Ok(json) => {
                 if let Some(list) = json.as_array() {
-                    for obj in list {
-                        if let Some(json) = obj.as_object() {
-                            self.verify_random_number_object(json, messages);
-                            // There isn't much sense having 500 errors/warnings for the same
-                            // random number object validation issue. Walk each item and verify
-                            // it is a valid json, break out on the first error/warning.
-                            if !messages.warnings.is_empty() || !messages.errors.is_empty() {
-                                break;
+                    if config::full_query_validation() {
+                        // Every object is walked, but each kind of problem is
+                        // only reported for the first object which has it.
+                        messages.deduplicated(|messages| {
+                            for json in list.iter().filter_map(Value::as_object) {
+                                self.verify_random_number_object(json, messages);
+                            }
+                        });
+                    } else {
+                        for obj in list {
+                            if let Some(json) = obj.as_object() {
+                                self.verify_random_number_object(json, messages);
+                                // There isn't much sense having 500 errors/warnings for the same
+                                // random number object validation issue. Walk each item and verify
+                                // it is a valid json, break out on the first error/warning.
+                                if !messages.warnings.is_empty() || !messages.errors.is_empty() {
+                                    break;
+                                }
                             }
                         }
                     }
-                    if list.len() != expected_count as usize {
+                    report_incorrect_length(list.len(), expected_count, messages);
+                } else if let Some(object) = json.as_object() {
+                    messages.warning("Top-level JSON is an object, not an array", "Invalid JSON");
+                    self.verify_random_number_object(object, messages);
+                    // A lone object stands in for an array of one; any other
+                    // count means the wrong number of rows were returned.
+                    if expected_count != 1 {
                         messages.error(
                             format!(
-                                "JSON array length of {} != expected length of {}",
-                                list.len(),
+                                "JSON object in place of an array of expected length {}",
                                 expected_count
                             ),
                             "Incorrect Length",
                         );
                     }
-                } else if let Some(object) = json.as_object() {
-                    messages.warning("Top-level JSON is an object, not an array", "Invalid JSON");
-                    self.verify_random_number_object(object, messages);
                 }
             }
         }
     }
 
+    /// Checks that the objects in the JSON array `response_body` correspond to
+    /// a reasonable number of distinct `id`s.
+    ///
+    /// Every object may be individually valid while the response as a whole
+    /// is not; e.g. a framework which hardcodes or caches a single `world` row
+    /// and returns it for every query, or a `cached_query` cache which answers
+    /// every key with the row it cached first. Random selection of 500 rows out of
+    /// 10,000 yields nearly 500 distinct `id`s, so we only warn when fewer
+    /// than half of the returned objects are distinct.
+    fn verify_distinct_ids(&self, response_body: &str, messages: &mut Messages) {
+        if let Ok(Value::Array(list)) = parse_json_with_lowercase_keys(response_body) {
+            let ids: HashSet<i64> = list
+                .iter()
+                .filter_map(|obj| obj.get("id"))
+                .filter_map(as_integer)
+                .collect();
+            if list.len() > 1 && !ids.is_empty() && ids.len() * 2 < list.len() {
+                messages.warning(
+                    format!(
+                        "Only {} distinct id(s) found in {} returned objects. Each query should select a random row.",
+                        ids.len(),
+                        list.len()
+                    ),
+                    "Duplicate Rows",
+                );
+            }
+        }
+    }
+
+    /// Checks that every `(id, randomNumber)` pair in `response_body` (either
+    /// a JSON object or an array of them) matches what is stored in the
+    /// `world` table, given as a map from `id` to `randomnumber`.
+    ///
+    /// Note: this must not be used for the `Updates` test type, since those
+    /// responses legitimately differ from what was stored at the time of
+    /// reading.
+    fn verify_random_numbers_match_database(
+        &self,
+        response_body: &str,
+        world: &HashMap<i32, i32>,
+        messages: &mut Messages,
+    ) {
+        if world.is_empty() {
+            // Nothing to compare against; e.g. the database is unreachable,
+            // which is reported elsewhere.
+            return;
+        }
+        let objects = match parse_json_with_lowercase_keys(response_body) {
+            Ok(Value::Array(list)) => list,
+            Ok(object) => vec![object],
+            Err(_) => return,
+        };
+        for object in objects {
+            let id = object.get("id").and_then(as_integer);
+            let random_number = object.get("randomnumber").and_then(as_integer);
+            if let (Some(id), Some(random_number)) = (id, random_number) {
+                if let Some(stored) = world.get(&(id as i32)) {
+                    if i64::from(*stored) != random_number {
+                        messages.error(
+                            format!(
+                                "id {} returned randomNumber {} but DB has {}",
+                                id, random_number, stored
+                            ),
+                            "Incorrect randomNumber",
+                        );
+                        // As in `verify_with_length`; one is enough.
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Verifies that the `queries` parameter being absent, empty, and `1` all
+    /// yield a single object; frameworks often parse these differently.
+    fn verify_single_object_cases(&self, url: &str, messages: &mut Messages) {
+        for case in [QueriesCase::Absent, QueriesCase::Empty, QueriesCase::One] {
+            let response = Request::get(&case.url(url))
+                .accepting(ContentType::Json.accept())
+                .fetch();
+            self.verify_single_object_case(case, response, messages);
+        }
+    }
+
+    /// Verifies the `response` for the given `QueriesCase`.
+    ///
+    /// Note: a server error for the empty value is only warned about, as it is
+    /// a parsing edge case rather than a wrong answer.
+    fn verify_single_object_case(
+        &self,
+        case: QueriesCase,
+        response: VerifierResult<Response>,
+        messages: &mut Messages,
+    ) {
+        match response {
+            Ok(response) => {
+                verify_declared_charset(&response, messages);
+                let body = String::from_utf8_lossy(&response.body);
+                messages.body(&body);
+                self.verify_with_length(&body, 1, messages);
+            }
+            Err(Non200Response(url, code, _)) if case == QueriesCase::Empty && code >= 500 => {
+                messages.warning(
+                    format!(
+                        "Server error {} from {} for an empty `queries` value, which should be treated as 1.",
+                        code, url
+                    ),
+                    "Empty queries value",
+                );
+            }
+            Err(e) => {
+                messages.error(e.to_string(), "Request error");
+            }
+        }
+    }
+
     /// Helper function for returning the translated query string.
     fn translate_query_count(&self, query_string: &str, min: i32, max: i32) -> i32 {
         if let Ok(queries) = i32::from_str(query_string) {
@@ -175,11 +384,144 @@ pub trait Query {
             min
         }
     }
+
+    /// Warns unless a query count can be appended to `url`; i.e. it ends with
+    /// e.g. `?queries=`. Without it, every count is requested from the wrong
+    /// path and answered with a 404.
+    fn verify_count_url(&self, url: &str, messages: &mut Messages) {
+        if !url.ends_with(['=', '?', '&']) {
+            messages.warning(
+                format!(
+                    "The url {} does not end with a query parameter (e.g. \"?queries=\") to which a count can be appended; check the ENDPOINT.",
+                    url
+                ),
+                "Invalid Endpoint",
+            );
+        }
+    }
 }
 
 //
 // PRIVATES
 //
+
+/// Reports a JSON array of `length` objects where `expected_count` were
+/// expected, telling a framework which did not clamp the `queries` parameter
+/// apart from one which returned too few rows for it.
+fn report_incorrect_length(length: usize, expected_count: i32, messages: &mut Messages) {
+    let expected_length = expected_count as usize;
+    if length == expected_length {
+        return;
+    }
+    if expected_count == MAX_QUERIES && length > expected_length {
+        messages.error(
+            format!(
+                "JSON array length of {} > maximum of {}; queries above {} should be clamped to {}",
+                length, MAX_QUERIES, MAX_QUERIES, MAX_QUERIES
+            ),
+            "Unclamped Query Count",
+        );
+    } else if expected_count == MIN_QUERIES && length == 0 {
+        messages.error(
+            format!(
+                "Empty JSON array; queries below {}, or which are not integers, should be clamped to {}",
+                MIN_QUERIES, MIN_QUERIES
+            ),
+            "Unclamped Query Count",
+        );
+    } else if length < expected_length {
+        messages.error(
+            format!(
+                "JSON array length of {} < expected length of {}; too few rows were returned",
+                length, expected_count
+            ),
+            "Too Few Rows",
+        );
+    } else {
+        messages.error(
+            format!(
+                "JSON array length of {} != expected length of {}",
+                length, expected_count
+            ),
+            "Incorrect Length",
+        );
+    }
+}
+
+fn verify_integer_field(value: &Value, field: &IntegerField, messages: &mut Messages) {
+    let mut parsed = value.as_i64();
+    if field.allow_int_string {
+        if let Some(str) = value.as_str() {
+            if let Ok(parsed_str) = i64::from_str(str) {
+                messages.warning(
+                    format!("Response key '{}' is int-string; should be int: {}. This may negatively affect performance by sending extra bytes.", field.key, str),
+                    "Extra Bytes"
+                );
+                parsed = Some(parsed_str);
+            }
+        }
+    }
+    if let Some(parsed) = parsed {
+        if let Some(min) = field.min.as_ref().filter(|min| parsed < min.limit) {
+            report_out_of_bounds(parsed, min, field, messages);
+        }
+        if let Some(max) = field.max.as_ref().filter(|max| parsed > max.limit) {
+            report_out_of_bounds(parsed, max, field, messages);
+        }
+    } else {
+        messages.error(
+            format!(
+                "Response key '{}' does not map to an integer: {}",
+                field.key, value
+            ),
+            "Invalid Value",
+        );
+    }
+}
+
+fn report_out_of_bounds(value: i64, bound: &Bound, field: &IntegerField, messages: &mut Messages) {
+    let limit = |bound: &Option<Bound>| {
+        bound
+            .as_ref()
+            .map(|bound| with_thousands_separators(bound.limit))
+            .unwrap_or_default()
+    };
+    let message = bound
+        .message
+        .replace("{value}", &value.to_string())
+        .replace("{min}", &limit(&field.min))
+        .replace("{max}", &limit(&field.max));
+    match bound.severity {
+        Severity::Warning => messages.warning(message, bound.short_message),
+        Severity::Error => messages.error(message, bound.short_message),
+    }
+}
+
+/// Formats `value` for display to humans; e.g. `10000` as `10,000`.
+fn with_thousands_separators(value: i64) -> String {
+    let digits = value.abs().to_string();
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap())
+        .collect();
+    let formatted = groups.join(",");
+    if value < 0 {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
+
+/// Gets the given `value` as an integer if it is either an int or an
+/// int-string.
+fn as_integer(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|str| i64::from_str(str).ok()))
+}
+
 struct _QueryTest {}
 impl Query for _QueryTest {}
 
@@ -201,13 +543,125 @@ mod tests {
         assert_eq!(query_test.translate_query_count("", 1, 500), 1);
     }
 
+    //
+    // verify_single_object_cases
+    //
+
+    use crate::error::VerifierError::Non200Response;
+    use crate::request::Response;
+    use crate::test_type::query::QueriesCase;
+    use std::time::Duration;
+
+    fn response(body: &str) -> Response {
+        Response {
+            body: body.as_bytes().to_vec(),
+            time_to_first_byte: Duration::default(),
+            total_time: Duration::default(),
+            content_type: None,
+        }
+    }
+
+    #[test]
+    fn it_should_build_urls_for_each_queries_case() {
+        let url = "http://tfb-server:8080/queries?queries=";
+        assert_eq!(
+            QueriesCase::Absent.url(url),
+            "http://tfb-server:8080/queries"
+        );
+        assert_eq!(QueriesCase::Empty.url(url), url);
+        assert_eq!(
+            QueriesCase::One.url(url),
+            "http://tfb-server:8080/queries?queries=1"
+        );
+        assert_eq!(
+            QueriesCase::Absent.url("http://tfb-server:8080/db?format=json&queries="),
+            "http://tfb-server:8080/db?format=json"
+        );
+    }
+
+    #[test]
+    fn it_should_accept_a_single_object_when_queries_is_absent() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        query_test.verify_single_object_case(
+            QueriesCase::Absent,
+            Ok(response("[{\"id\":1234,\"randomNumber\":4321}]")),
+            &mut messages,
+        );
+        assert!(messages.errors.is_empty());
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_warn_on_server_error_for_empty_queries() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        query_test.verify_single_object_case(
+            QueriesCase::Empty,
+            Err(Non200Response(
+                "http://tfb-server:8080/queries?queries=".to_string(),
+                500,
+                String::new(),
+            )),
+            &mut messages,
+        );
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("empty `queries` value"));
+    }
+
+    #[test]
+    fn it_should_error_on_wrong_length_for_explicit_one() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        query_test.verify_single_object_case(
+            QueriesCase::One,
+            Ok(response(
+                "[{\"id\":1,\"randomNumber\":2},{\"id\":3,\"randomNumber\":4}]",
+            )),
+            &mut messages,
+        );
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0].message.contains("expected length of 1"));
+
+        let mut messages = Messages::default();
+        query_test.verify_single_object_case(
+            QueriesCase::One,
+            Err(Non200Response(
+                "http://tfb-server:8080/queries?queries=1".to_string(),
+                500,
+                String::new(),
+            )),
+            &mut messages,
+        );
+        assert_eq!(messages.errors.len(), 1);
+    }
+
+    #[test]
+    fn it_should_warn_on_url_without_count_parameter() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        query_test.verify_count_url("http://tfb-server:8080/queries?queries=", &mut messages);
+        query_test.verify_count_url("http://tfb-server:8080/queries?", &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        query_test.verify_count_url("http://tfb-server:8080/queries", &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+    }
+
     //
     // verify_random_number_object
     //
 
+    use crate::test_type::query::{
+        _QueryTest, report_incorrect_length, verify_object_schema, with_thousands_separators,
+        world_object_schema, Query,
+    };
     use crate::verification::Messages;
-    use crate::test_type::query::{Query, _QueryTest};
     use serde_json::Value;
+    use std::collections::HashMap;
 
     #[test]
     fn it_should_succeed_on_valid_db_object() {
@@ -329,6 +783,91 @@ mod tests {
             .contains("should be between 1 and 10,000"));
     }
 
+    #[test]
+    fn it_should_warn_on_id_below_one() {
+        let json = serde_json::from_str::<Value>("{\"id\":0,\"randomnumber\":4321}").unwrap();
+        let query_test = _QueryTest {};
+
+        let mut messages = Messages::default();
+        query_test.verify_random_number_object(json.as_object().unwrap(), &mut messages);
+
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert_eq!(
+            messages.warnings[0].message,
+            "Response key 'id' should be between 1 and 10,000: 0"
+        );
+    }
+
+    #[test]
+    fn it_should_accept_zero_based_ids() {
+        let schema = world_object_schema(0, 10_000, 10_000);
+        for body in [
+            "{\"id\":0,\"randomnumber\":4321}",
+            "{\"id\":9999,\"randomnumber\":4321}",
+        ] {
+            let json = serde_json::from_str::<Value>(body).unwrap();
+            let mut messages = Messages::default();
+            verify_object_schema(json.as_object().unwrap(), &schema, &mut messages);
+
+            assert!(messages.warnings.is_empty());
+        }
+
+        let json = serde_json::from_str::<Value>("{\"id\":10000,\"randomnumber\":4321}").unwrap();
+        let mut messages = Messages::default();
+        verify_object_schema(json.as_object().unwrap(), &schema, &mut messages);
+
+        assert_eq!(
+            messages.warnings[0].message,
+            "Response key 'id' should be between 0 and 9,999: 10000"
+        );
+    }
+
+    #[test]
+    fn it_should_accept_one_based_ids() {
+        let schema = world_object_schema(1, 10_000, 10_000);
+        for body in [
+            "{\"id\":1,\"randomnumber\":4321}",
+            "{\"id\":10000,\"randomnumber\":4321}",
+        ] {
+            let json = serde_json::from_str::<Value>(body).unwrap();
+            let mut messages = Messages::default();
+            verify_object_schema(json.as_object().unwrap(), &schema, &mut messages);
+
+            assert!(messages.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn it_should_accept_a_differently_sized_world() {
+        let schema = world_object_schema(1, 100_000, 1_000);
+        let json = serde_json::from_str::<Value>("{\"id\":100000,\"randomnumber\":1000}").unwrap();
+        let mut messages = Messages::default();
+        verify_object_schema(json.as_object().unwrap(), &schema, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let json = serde_json::from_str::<Value>("{\"id\":100001,\"randomnumber\":1001}").unwrap();
+        let mut messages = Messages::default();
+        verify_object_schema(json.as_object().unwrap(), &schema, &mut messages);
+        assert_eq!(
+            messages.warnings[0].message,
+            "Response key 'id' should be between 1 and 100,000: 100001"
+        );
+        assert_eq!(
+            messages.warnings[1].message,
+            "Response key `randomNumber` is over 1,000. This may negatively affect performance by sending extra bytes."
+        );
+    }
+
+    #[test]
+    fn it_should_format_thousands_separators() {
+        assert_eq!(with_thousands_separators(1), "1");
+        assert_eq!(with_thousands_separators(999), "999");
+        assert_eq!(with_thousands_separators(10_000), "10,000");
+        assert_eq!(with_thousands_separators(1_234_567), "1,234,567");
+        assert_eq!(with_thousands_separators(-10_000), "-10,000");
+    }
+
     #[test]
     fn it_should_warn_on_random_number_above_ten_thousand() {
         let json = serde_json::from_str::<Value>("{\"id\":1234,\"randomnumber\":43210}").unwrap();
@@ -406,4 +945,94 @@ mod tests {
             .message
             .contains("JSON is an object, not an array"));
     }
+
+    #[test]
+    fn it_should_tell_unclamped_counts_from_too_few_rows() {
+        let reported = |length, expected_count| {
+            let mut messages = Messages::default();
+            report_incorrect_length(length, expected_count, &mut messages);
+            messages
+                .errors
+                .iter()
+                .map(|error| error.message.clone())
+                .collect::<Vec<String>>()
+                .join("")
+        };
+
+        assert!(reported(500, 500).is_empty());
+        assert!(reported(501, 500).contains("should be clamped to 500"));
+        assert!(reported(0, 1).contains("should be clamped to 1"));
+        assert!(reported(1, 2).contains("too few rows"));
+        assert!(reported(3, 2).contains("!= expected length of 2"));
+    }
+
+    //
+    // verify_distinct_ids
+    //
+
+    #[test]
+    fn it_should_pass_on_distinct_ids() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        query_test.verify_distinct_ids(
+            "[{\"id\":1234,\"randomnumber\":4321},{\"id\":4567,\"randomnumber\":1234}]",
+            &mut messages,
+        );
+
+        assert!(messages.errors.is_empty());
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_warn_on_repeated_ids() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        let row = "{\"id\":1234,\"randomnumber\":4321}";
+        let body = format!("[{}]", vec![row; 20].join(","));
+        query_test.verify_distinct_ids(&body, &mut messages);
+
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .contains("Only 1 distinct id(s) found in 20 returned objects"));
+    }
+
+    //
+    // verify_random_numbers_match_database
+    //
+
+    #[test]
+    fn it_should_pass_when_random_numbers_match_database() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        let mut world = HashMap::new();
+        world.insert(42, 17);
+        world.insert(43, 18);
+        query_test.verify_random_numbers_match_database(
+            "[{\"id\":42,\"randomNumber\":17},{\"id\":\"43\",\"randomNumber\":18}]",
+            &world,
+            &mut messages,
+        );
+
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_error_when_random_number_differs_from_database() {
+        let query_test = _QueryTest {};
+        let mut messages = Messages::default();
+        let mut world = HashMap::new();
+        world.insert(42, 17);
+        query_test.verify_random_numbers_match_database(
+            "{\"id\":42,\"randomNumber\":99}",
+            &world,
+            &mut messages,
+        );
+
+        assert_eq!(messages.errors.len(), 1);
+        assert!(messages.errors[0]
+            .message
+            .contains("id 42 returned randomNumber 99 but DB has 17"));
+    }
 }
@@ -1,20 +1,27 @@
 use crate::benchmark::BenchmarkCommands;
+use crate::config;
 use crate::database::DatabaseInterface;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::query::Query;
-use crate::test_type::Executor;
+use crate::explain::ExpectedCounts;
+use crate::request::{
+    get_compression, get_response_body, get_response_headers, verify_connection_reuse, Compression,
+    ContentType, Request,
+};
+use crate::test_type::query::{Query, MAX_QUERIES, MIN_QUERIES};
+use crate::test_type::{parse_json_with_lowercase_keys, verify_response_size, Executor};
 use crate::verification::Messages;
 use std::cmp::min;
 
 pub struct MultiQuery {
     pub concurrency_levels: Vec<u32>,
+    /// The times each burst of requests is sent while counting queries.
+    pub repetitions: u32,
     pub database_verifier: Box<dyn DatabaseInterface>,
 }
 impl Query for MultiQuery {}
 impl Executor for MultiQuery {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "query"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -33,6 +40,14 @@ impl Executor for MultiQuery {
         })
     }
 
+    fn expected_counts(&self) -> Option<ExpectedCounts> {
+        Some(self.counts())
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Json)
+    }
+
     /// Validates the response is a JSON array of the proper length, each JSON
     /// Object in the array has keys 'id' and 'randomNumber', and these keys
     /// map to integer-ish types.
@@ -47,39 +62,61 @@ impl Executor for MultiQuery {
     /// framework from being benchmarked.
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
+        self.database_verifier.verify_world_row_count(&mut messages);
+        self.database_verifier.verify_schema(&mut messages);
 
         // Initialization for query counting
-        let repetitions = 2;
-        let concurrency = *self.concurrency_levels.iter().max().unwrap();
-        let expected_queries = 20 * repetitions * concurrency;
-        let expected_rows = expected_queries;
+        let ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows,
+            ..
+        } = self.counts();
 
         // Because this test type is going to make a LOT of requests with a reasonably long timeout,
         // we use `get_response_headers` as a sentinel. If a `CurlError` is thrown, then we do not
         // perform any of the follow-up requests to conserve time.
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Json.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
             self.verify_headers(&response_headers, &url, ContentType::Json, &mut messages);
+            verify_connection_reuse(url, &mut messages);
+            self.verify_count_url(url, &mut messages);
 
-            let test_cases = ["2", "0", "foo", "501", ""];
-            let min = 1;
-            let max = 500;
+            // The absent, empty, and `1` values are checked separately below.
+            let test_cases = ["2", "0", "foo", "501"];
+            let min = MIN_QUERIES;
+            let max = MAX_QUERIES;
+            let world = self
+                .database_verifier
+                .get_all_from_world_table(&mut messages);
 
             for test_case in test_cases.iter() {
                 let expected_length = self.translate_query_count(*test_case, min, max);
                 let count_url = format!("{}{}", url, test_case);
 
-                if let Some(response_body) = get_response_body(&count_url, &mut messages) {
+                if let Some(response_body) =
+                    get_response_body(&count_url, ContentType::Json.accept(), &mut messages)
+                {
                     messages.body(&response_body);
                     self.verify_with_length(&response_body, expected_length, &mut messages);
+                    self.verify_distinct_ids(&response_body, &mut messages);
+                    self.verify_random_numbers_match_database(
+                        &response_body,
+                        &world,
+                        &mut messages,
+                    );
 
                     // Only check update changes if we're testing the highest number of
                     // queries, to ensure that we don't accidentally FAIL for a query
                     // that only updates 1 item and happens to set its randomNumber to
                     // the same value it previously held
                     if expected_length == max {
+                        verify_minimal_size(&response_body, &mut messages);
                         self.database_verifier.verify_queries_count(
-                            &format!("{}20", url),
+                            &Request::get(&format!("{}20", url)),
                             "world",
                             concurrency,
                             repetitions,
@@ -87,7 +124,7 @@ impl Executor for MultiQuery {
                             &mut messages,
                         );
                         self.database_verifier.verify_rows_count(
-                            &format!("{}20", url),
+                            &Request::get(&format!("{}20", url)),
                             "world",
                             concurrency,
                             repetitions,
@@ -98,19 +135,52 @@ impl Executor for MultiQuery {
                     }
                 }
             }
+            self.verify_single_object_cases(url, &mut messages);
+            if config::check_compression() {
+                self.verify_compression(url, &mut messages);
+            }
         }
 
         Ok(messages)
     }
 }
 impl MultiQuery {
+    /// Counting is done with `queries=20`, so each request should issue 20
+    /// queries of one row apiece.
+    fn counts(&self) -> ExpectedCounts {
+        let repetitions = self.repetitions;
+        let concurrency = *self.concurrency_levels.iter().max().unwrap();
+        let expected_queries = 20 * repetitions * concurrency;
+
+        ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows: expected_queries,
+            expected_updates: None,
+        }
+    }
+
+    /// Requests the largest response, offering to accept gzip, and warns if
+    /// the server compressed it to little effect.
+    fn verify_compression(&self, url: &str, messages: &mut Messages) {
+        let max_url = format!("{}{}", url, MAX_QUERIES);
+        match get_compression(&max_url, ContentType::Json.accept()) {
+            Ok(compression) => report_compression(&compression, messages),
+            Err(e) => messages.warning(
+                format!("Could not request a compressed response: {}", e),
+                "Compression Unverified",
+            ),
+        }
+    }
+
     fn get_wrk_command(&self, url: &str, duration: u32, concurrency: u32) -> Vec<String> {
         vec![
             "wrk",
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Json.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -123,6 +193,122 @@ impl MultiQuery {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Sizes the JSON `response_body` against its own compact serialization, the
+/// least it could be for the rows it holds; see `verify_response_size`.
+fn verify_minimal_size(response_body: &str, messages: &mut Messages) {
+    if let Ok(json) = parse_json_with_lowercase_keys(response_body) {
+        let baseline = json.to_string().len();
+        verify_response_size("multi_query", response_body.len(), baseline, messages);
+    }
+}
+
+/// Warns when a gzipped response was transferred at more than 90% of its
+/// decompressed size; compressing it costs the server for next to no saving.
+/// Responses which are not gzipped, or empty, are not compared.
+fn report_compression(compression: &Compression, messages: &mut Messages) {
+    if compression.decompressed_bytes == 0 {
+        return;
+    }
+    let gzipped = compression
+        .encoding
+        .as_deref()
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("gzip"));
+    let ratio = compression.transferred_bytes as f64 / compression.decompressed_bytes as f64;
+    if gzipped && ratio > 0.9 {
+        messages.warning(
+            format!(
+                "Response was gzipped to {} bytes from {} bytes; compression saves next to nothing.",
+                compression.transferred_bytes, compression.decompressed_bytes
+            ),
+            "Ineffective Compression",
+        );
+    }
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::database::stub::Stub;
+    use crate::request::Compression;
+    use crate::test_type::query::multi_query::{
+        report_compression, verify_minimal_size, MultiQuery,
+    };
+    use crate::test_type::Executor;
+    use crate::verification::Messages;
+
+    #[test]
+    fn it_should_expect_twenty_queries_per_request() {
+        let multi_query = MultiQuery {
+            concurrency_levels: vec![16, 512, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Stub::default()),
+        };
+        let counts = multi_query.expected_counts().unwrap();
+        assert_eq!(counts.concurrency, 512);
+        assert_eq!(counts.repetitions, 2);
+        assert_eq!(counts.expected_queries, 20 * 2 * 512);
+        assert_eq!(counts.expected_rows, 20 * 2 * 512);
+        assert_eq!(counts.expected_updates, None);
+    }
+
+    #[test]
+    fn it_should_warn_on_ineffective_compression() {
+        let compression = |encoding: Option<&str>, transferred_bytes| Compression {
+            encoding: encoding.map(str::to_string),
+            transferred_bytes,
+            decompressed_bytes: 10_000,
+        };
+
+        let mut messages = Messages::default();
+        report_compression(&compression(Some("gzip"), 2_500), &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let mut messages = Messages::default();
+        report_compression(&compression(None, 10_000), &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        let mut messages = Messages::default();
+        report_compression(&compression(Some("gzip"), 9_800), &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+
+        let mut messages = Messages::default();
+        let empty = Compression {
+            encoding: Some("gzip".to_string()),
+            transferred_bytes: 20,
+            decompressed_bytes: 0,
+        };
+        report_compression(&empty, &mut messages);
+        assert!(messages.warnings.is_empty());
+    }
+
+    #[test]
+    fn it_should_size_responses_against_their_compact_json() {
+        let rows: Vec<String> = (1..=500)
+            .map(|id| format!("{{\"id\":{},\"randomNumber\":{}}}", id, id))
+            .collect();
+        let compact = format!("[{}]", rows.join(","));
+        let mut messages = Messages::default();
+        verify_minimal_size(&compact, &mut messages);
+        assert!(messages.warnings.is_empty());
+        assert_eq!(messages.extra_bytes, vec![("multi_query".to_string(), 0)]);
+
+        let padded = compact.replace(",", ",\n                ");
+        let mut messages = Messages::default();
+        verify_minimal_size(&padded, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
     }
 }
@@ -1,21 +1,34 @@
 use crate::benchmark::BenchmarkCommands;
+use crate::config;
 use crate::database::DatabaseInterface;
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
+use crate::explain::ExpectedCounts;
+use crate::request::{
+    get_response_body, get_response_headers, verify_connection_reuse, ContentType, Request,
+    ResponseHeaders,
+};
 use crate::test_type::query::Query;
 use crate::test_type::Executor;
+use crate::test_type::{
+    invalid_json_message, parse_json_with_lowercase_keys, verify_no_byte_order_mark,
+    verify_response_headers,
+};
 use crate::verification::Messages;
-use serde_json::Value;
 use std::cmp::min;
+use std::sync::mpsc::Receiver;
+use std::sync::{mpsc, Arc};
+use threadpool::ThreadPool;
 
 pub struct SingleQuery {
     pub concurrency_levels: Vec<u32>,
+    /// The times each burst of requests is sent while counting queries.
+    pub repetitions: u32,
     pub database_verifier: Box<dyn DatabaseInterface>,
 }
 impl Query for SingleQuery {}
 impl Executor for SingleQuery {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "db"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -34,27 +47,74 @@ impl Executor for SingleQuery {
         })
     }
 
+    fn expected_counts(&self) -> Option<ExpectedCounts> {
+        Some(self.counts())
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Json)
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
+        self.database_verifier.verify_world_row_count(&mut messages);
+        self.database_verifier.verify_schema(&mut messages);
 
         // Because this test type is going to make a LOT of requests with a reasonably long timeout,
         // we use `get_response_headers` as a sentinel. If a `CurlError` is thrown, then we do not
         // perform any of the follow-up requests to conserve time.
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Json.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
-            self.verify_headers(&response_headers, &url, ContentType::Json, &mut messages);
-            if let Some(response_body) = get_response_body(&url, &mut messages) {
-                messages.body(&response_body);
-
-                // Initialization for query counting
-                let repetitions = 2;
-                let concurrency = *self.concurrency_levels.iter().max().unwrap();
-                let expected_queries = repetitions * concurrency;
-                let expected_rows = expected_queries;
+            // The header checks wait out the cached `Date` retest, so they run
+            // alongside the body checks rather than before them.
+            let header_checks = verify_headers_in_background(url, &response_headers);
+            let response_body = get_response_body(&url, ContentType::Json.accept(), &mut messages);
+            if let Some(response_body) = &response_body {
+                messages.body(response_body);
+                verify_no_byte_order_mark(response_body, &mut messages);
+                self.verify_single_query(response_body, &mut messages);
+                let samples = config::concurrent_samples();
+                if samples > 0 {
+                    let url = url.to_string();
+                    self.verify_concurrent_samples(
+                        samples,
+                        move || {
+                            Request::get(&url)
+                                .accepting(ContentType::Json.accept())
+                                .send()
+                                .ok()
+                                .map(|body| String::from_utf8_lossy(&body).to_string())
+                        },
+                        &mut messages,
+                    );
+                }
+                self.verify_random_numbers_match_database(
+                    response_body,
+                    &self
+                        .database_verifier
+                        .get_all_from_world_table(&mut messages),
+                    &mut messages,
+                );
+            }
 
-                self.verify_single_query(&response_body, &mut messages);
+            // Every request to the framework is answered with queries, so the
+            // header checks must be done before counting starts; the counts
+            // themselves stay sequential (see `DatabaseInterface`).
+            if let Ok(header_messages) = header_checks.recv() {
+                messages.merge(header_messages);
+            }
+            if response_body.is_some() {
+                let ExpectedCounts {
+                    concurrency,
+                    repetitions,
+                    expected_queries,
+                    expected_rows,
+                    ..
+                } = self.counts();
                 self.database_verifier.verify_queries_count(
-                    url,
+                    &Request::get(url),
                     "world",
                     concurrency,
                     repetitions,
@@ -62,7 +122,7 @@ impl Executor for SingleQuery {
                     &mut messages,
                 );
                 self.database_verifier.verify_rows_count(
-                    url,
+                    &Request::get(url),
                     "world",
                     concurrency,
                     repetitions,
@@ -77,15 +137,35 @@ impl Executor for SingleQuery {
     }
 }
 impl SingleQuery {
+    /// One query, selecting one row, per request.
+    fn counts(&self) -> ExpectedCounts {
+        let repetitions = self.repetitions;
+        let concurrency = *self.concurrency_levels.iter().max().unwrap();
+        let expected_queries = repetitions * concurrency;
+
+        ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows: expected_queries,
+            expected_updates: None,
+        }
+    }
+
     fn verify_single_query(&self, response_body: &str, messages: &mut Messages) {
-        match serde_json::from_str::<Value>(&response_body.to_lowercase()) {
+        match parse_json_with_lowercase_keys(response_body) {
             Err(e) => {
-                messages.error(format!("Invalid JSON: {:?}", e), "Invalid JSON");
+                messages.error(invalid_json_message(response_body, &e), "Invalid JSON");
             }
             Ok(mut json) => {
                 if let Some(arr) = json.as_array() {
+                    // The length tells a single row wrapped in an array apart
+                    // from many rows, which is a worse bug.
                     messages.warning(
-                        "Response is a JSON array. Expected JSON object (e.g. [] vs {})",
+                        format!(
+                            "Response is a JSON array of {} element(s). Expected JSON object (e.g. [] vs {{}}); return the single row as a bare object.",
+                            arr.len()
+                        ),
                         "Expected JSON object",
                     );
                     if let Some(first) = arr.get(0) {
@@ -104,13 +184,60 @@ impl SingleQuery {
         }
     }
 
+    /// Fetches `samples` responses concurrently with `fetch` and verifies each
+    /// as with `verify_single_query`, reporting how many were malformed.
+    ///
+    /// A framework which shares a response buffer between requests may only
+    /// produce a malformed object under concurrency, which a lone request
+    /// will not catch. Failed requests are not counted; those are reported by
+    /// the query counting.
+    fn verify_concurrent_samples<F>(&self, samples: u32, fetch: F, messages: &mut Messages)
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        let fetch = Arc::new(fetch);
+        let (sender, receiver) = mpsc::channel();
+        let pool = ThreadPool::new(num_cpus::get());
+        for _ in 0..samples {
+            let fetch = Arc::clone(&fetch);
+            let sender = sender.clone();
+            pool.execute(move || {
+                if let Some(body) = fetch() {
+                    let _ = sender.send(body);
+                }
+            });
+        }
+        pool.join();
+        drop(sender);
+
+        let mut fetched = 0;
+        let mut malformed = 0;
+        for body in receiver {
+            fetched += 1;
+            let mut sample_messages = Messages::silent();
+            self.verify_single_query(&body, &mut sample_messages);
+            if !sample_messages.errors.is_empty() {
+                malformed += 1;
+            }
+        }
+        if malformed > 0 {
+            messages.error(
+                format!(
+                    "{} of {} responses fetched concurrently were malformed.",
+                    malformed, fetched
+                ),
+                "Malformed Under Concurrency",
+            );
+        }
+    }
+
     fn get_wrk_command(&self, url: &str, duration: u32, concurrency: u32) -> Vec<String> {
         vec![
             "wrk",
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Json.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -123,10 +250,34 @@ impl SingleQuery {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
     }
 }
 
+//
+// PRIVATES
+//
+
+/// Verifies the `headers` of `url`, and that its connection is reused, on a
+/// thread of its own; the returned receiver yields the results once done.
+fn verify_headers_in_background(url: &str, headers: &ResponseHeaders) -> Receiver<Messages> {
+    let url = url.to_string();
+    let headers = headers.clone();
+    let (sender, receiver) = mpsc::channel();
+    ThreadPool::new(1).execute(move || {
+        let mut messages = Messages::new(&url);
+        messages.headers(&headers);
+        verify_response_headers(&headers, &url, ContentType::Json, &mut messages);
+        verify_connection_reuse(&url, &mut messages);
+        let _ = sender.send(messages);
+    });
+
+    receiver
+}
+
 //
 // TESTS
 //
@@ -135,12 +286,15 @@ impl SingleQuery {
 mod tests {
     use crate::database::mysql::Mysql;
     use crate::test_type::query::single_query::SingleQuery;
+    use crate::test_type::Executor;
     use crate::verification::Messages;
+    use std::sync::atomic::{AtomicU32, Ordering};
 
     #[test]
     fn it_should_pass_simply() {
         let query = SingleQuery {
             concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            repetitions: 2,
             database_verifier: Box::new(Mysql {}),
         };
         let mut messages = Messages::default();
@@ -148,4 +302,98 @@ mod tests {
         assert!(messages.errors.is_empty());
         assert!(messages.warnings.is_empty());
     }
+
+    #[test]
+    fn it_should_warn_with_the_length_of_an_array() {
+        let query = SingleQuery {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let rows: Vec<String> = (1..=20)
+            .map(|id| format!("{{\"id\":{},\"randomNumber\":8952}}", id))
+            .collect();
+        let mut messages = Messages::default();
+        query.verify_single_query(&format!("[{}]", rows.join(",")), &mut messages);
+        assert!(messages.errors.is_empty());
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .starts_with("Response is a JSON array of 20 element(s)."));
+        assert!(messages.warnings[0].message.contains("bare object"));
+    }
+
+    #[test]
+    fn it_should_count_malformed_concurrent_samples() {
+        let query = SingleQuery {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let fetches = AtomicU32::new(0);
+        let mut messages = Messages::default();
+        query.verify_concurrent_samples(
+            9,
+            move || {
+                // The first three responses are cut short.
+                if fetches.fetch_add(1, Ordering::SeqCst) < 3 {
+                    Some("{\"id\": 2354,\"random".to_string())
+                } else {
+                    Some("{\"id\": 2354,\"randomNumber\":8952}".to_string())
+                }
+            },
+            &mut messages,
+        );
+
+        assert_eq!(messages.errors.len(), 1);
+        assert_eq!(
+            messages.errors[0].message,
+            "3 of 9 responses fetched concurrently were malformed."
+        );
+    }
+
+    #[test]
+    fn it_should_pass_well_formed_concurrent_samples() {
+        let query = SingleQuery {
+            concurrency_levels: vec![16, 32, 64, 128, 256, 512],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let mut messages = Messages::default();
+        query.verify_concurrent_samples(
+            9,
+            || Some("{\"id\": 2354,\"randomNumber\":8952}".to_string()),
+            &mut messages,
+        );
+
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_expect_one_row_per_query() {
+        let single_query = SingleQuery {
+            concurrency_levels: vec![16, 512, 32],
+            repetitions: 2,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let counts = single_query.expected_counts().unwrap();
+        assert_eq!(counts.concurrency, 512);
+        assert_eq!(counts.repetitions, 2);
+        assert_eq!(counts.expected_queries, 2 * 512);
+        assert_eq!(counts.expected_rows, 2 * 512);
+        assert_eq!(counts.expected_updates, None);
+    }
+
+    #[test]
+    fn it_should_scale_expected_counts_with_repetitions() {
+        let single_query = SingleQuery {
+            concurrency_levels: vec![16, 512, 32],
+            repetitions: 5,
+            database_verifier: Box::new(Mysql {}),
+        };
+        let counts = single_query.expected_counts().unwrap();
+        assert_eq!(counts.repetitions, 5);
+        assert_eq!(counts.expected_queries, 5 * 512);
+        assert_eq!(counts.expected_rows, 5 * 512);
+    }
 }
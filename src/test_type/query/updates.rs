@@ -1,21 +1,36 @@
 use crate::benchmark::BenchmarkCommands;
-use crate::database::DatabaseInterface;
+use crate::config::UpdateMethod;
+use crate::database::{report_counting_error, DatabaseInterface};
 use crate::error::VerifierResult;
-use crate::request::{get_response_body, get_response_headers, ContentType};
-use crate::test_type::query::Query;
+use crate::explain::ExpectedCounts;
+use crate::request::{
+    get_response_body_for, get_response_headers, get_response_headers_for, verify_connection_reuse,
+    ContentType, HttpMethod, Request,
+};
+use crate::test_type::query::{Query, MAX_QUERIES, MIN_QUERIES};
 use crate::test_type::Executor;
 use crate::verification::Messages;
 use std::cmp;
 use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+
+/// The bursts of update requests over which changed rows are accumulated; an
+/// update may set the value a row already held, so a single burst can come up
+/// short by chance.
+const UPDATE_DETECTION_BURSTS: u32 = 2;
 
 pub struct Updates {
     pub concurrency_levels: Vec<u32>,
+    /// The times each burst of requests is sent while counting queries.
+    pub repetitions: u32,
     pub database_verifier: Box<dyn DatabaseInterface>,
+    pub update_method: UpdateMethod,
+    pub update_body_template: String,
 }
 impl Query for Updates {}
 impl Executor for Updates {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "update"
     }
 
     fn retrieve_benchmark_commands(&self, url: &str) -> VerifierResult<BenchmarkCommands> {
@@ -34,49 +49,85 @@ impl Executor for Updates {
         })
     }
 
+    fn expected_counts(&self) -> Option<ExpectedCounts> {
+        Some(self.counts())
+    }
+
+    /// Sends a single update with the `update_method`, which a GET may not be.
+    fn smoke_test(&self, url: &str) -> VerifierResult<()> {
+        self.update_request(url, "1").send().map(|_| ())
+    }
+
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::Json)
+    }
+
+    /// Verifies the headers of the response to a single update, sent with the
+    /// `update_method` as `smoke_test` does.
+    fn verify_headers_only(&self, url: &str) -> VerifierResult<Messages> {
+        let mut messages = Messages::new(url);
+        let request = self.update_request(url, "1");
+        if let Ok(headers) = get_response_headers_for(&request, &mut messages) {
+            messages.headers(&headers);
+            self.verify_headers(&headers, url, ContentType::Json, &mut messages);
+        }
+
+        Ok(messages)
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
+        self.database_verifier.verify_schema(&mut messages);
 
         let test_cases = ["2", "0", "foo", "501", ""];
 
         // Initialization for query counting
-        let repetitions = 2;
-        let concurrency = *self.concurrency_levels.iter().max().unwrap();
-        let expected_rows = 20 * repetitions * concurrency;
-        let expected_updates = expected_rows;
-        // Note: frameworks are allowed to do the updates in a single bulk query so some frameworks will
-        // have only 1 update query for every 20 select queries. so we only need to verify that at least
-        // this number of queries were performed.
-        // i.e. if concurrency = 1, then we will have:
-        // 20 * 2 = 40 rows updated
-        // 20 * 2 = 40 select queries
-        // 1 * 2 = 2 update queries = 42 expected queries in total
-        let expected_queries = expected_rows / 20;
-        let min = 1;
-        let max = 500;
+        let counts = self.counts();
+        let ExpectedCounts {
+            concurrency,
+            repetitions,
+            expected_queries,
+            expected_rows,
+            ..
+        } = counts;
+        let expected_updates = counts.expected_updates.unwrap_or(expected_rows);
+        let min = MIN_QUERIES;
+        let max = MAX_QUERIES;
 
         // Because this test type is going to make a LOT of requests with a reasonably long timeout,
         // we use `get_response_headers` as a sentinel. If a `CurlError` is thrown, then we do not
         // perform any of the follow-up requests to conserve time.
-        if let Ok(response_headers) = get_response_headers(&url, &mut messages) {
+        if let Ok(response_headers) =
+            get_response_headers(&url, ContentType::Json.accept(), &mut messages)
+        {
             messages.headers(&response_headers);
             self.verify_headers(&response_headers, &url, ContentType::Json, &mut messages);
+            verify_connection_reuse(url, &mut messages);
+            // Only a GET appends the count to the url.
+            if let UpdateMethod::Get = self.update_method {
+                self.verify_count_url(url, &mut messages);
+            }
 
             for test_case in test_cases.iter() {
-                let expected_length = self.translate_query_count(*test_case, min, max);
-                let count_url = format!("{}{}", url, test_case);
-
-                if let Some(response_body) = get_response_body(&count_url, &mut messages) {
+                let request = self.update_request(url, test_case);
+                if let Some(response_body) = get_response_body_for(&request, &mut messages) {
                     messages.body(&response_body);
-                    self.verify_with_length(&response_body, expected_length, &mut messages);
+                    let expected_length = self.verify_updates_length(
+                        *test_case,
+                        &response_body,
+                        min,
+                        max,
+                        &mut messages,
+                    );
 
                     // Only check update changes if we're testing the highest number of
                     // queries, to ensure that we don't accidentally FAIL for a query
                     // that only updates 1 item and happens to set its randomNumber to
                     // the same value it previously held
                     if expected_length == max {
+                        let request = self.update_request(url, "20");
                         self.database_verifier.verify_queries_count(
-                            &format!("{}20", url),
+                            &request,
                             "world",
                             concurrency,
                             repetitions,
@@ -84,7 +135,7 @@ impl Executor for Updates {
                             &mut messages,
                         );
                         self.database_verifier.verify_rows_count(
-                            &format!("{}20", url),
+                            &request,
                             "world",
                             concurrency,
                             repetitions,
@@ -93,19 +144,14 @@ impl Executor for Updates {
                             &mut messages,
                         );
                         self.verify_updates_count(
-                            &format!("{}20", url),
+                            &request,
                             "world",
                             concurrency,
                             repetitions,
                             expected_updates,
                             &mut messages,
                         );
-                        self.verify_updates(
-                            &format!("{}20", url),
-                            concurrency,
-                            repetitions,
-                            &mut messages,
-                        )
+                        self.verify_updates(&request, concurrency, repetitions, &mut messages)
                     }
                 }
             }
@@ -115,31 +161,125 @@ impl Executor for Updates {
     }
 }
 impl Updates {
+    /// Counting is done with `queries=20`: 20 rows selected and updated per
+    /// request, though the updates may be batched into fewer queries.
+    fn counts(&self) -> ExpectedCounts {
+        let repetitions = self.repetitions;
+        let concurrency = *self.concurrency_levels.iter().max().unwrap();
+        let expected_rows = 20 * repetitions * concurrency;
+
+        ExpectedCounts {
+            concurrency,
+            repetitions,
+            // Note: frameworks are allowed to do the updates in a single bulk query so some frameworks will
+            // have only 1 update query for every 20 select queries. so we only need to verify that at least
+            // this number of queries were performed.
+            // i.e. if concurrency = 1, then we will have:
+            // 20 * 2 = 40 rows updated
+            // 20 * 2 = 40 select queries
+            // 1 * 2 = 2 update queries = 42 expected queries in total
+            expected_queries: expected_rows / 20,
+            expected_rows,
+            expected_updates: Some(expected_rows),
+        }
+    }
+
+    /// Gets the `Request` which performs `queries` updates against `url`,
+    /// as per the `update_method`.
+    fn update_request(&self, url: &str, queries: &str) -> Request {
+        let method = match self.update_method {
+            UpdateMethod::Get => {
+                return Request::get(&format!("{}{}", url, queries))
+                    .accepting(ContentType::Json.accept())
+            }
+            UpdateMethod::Post => HttpMethod::Post,
+            UpdateMethod::Put => HttpMethod::Put,
+        };
+        let body = self.update_body_template.replace("{queries}", queries);
+
+        Request::with_body(url, method, body.into_bytes()).accepting(ContentType::Json.accept())
+    }
+
+    /// Verifies that `response_body` holds one updated object for each of the
+    /// queries requested by `test_case`, after clamping it between `min` and
+    /// `max`. Returns the expected number of objects.
+    fn verify_updates_length(
+        &self,
+        test_case: &str,
+        response_body: &str,
+        min: i32,
+        max: i32,
+        messages: &mut Messages,
+    ) -> i32 {
+        let expected_length = self.translate_query_count(test_case, min, max);
+        self.verify_with_length(response_body, expected_length, messages);
+
+        expected_length
+    }
+
     /// Counts all the updates that the datastore has on record, then performs
-    /// `concurrency` requests for `url` `repetitions` times, then checks all
+    /// `concurrency` copies of `request` `repetitions` times, then checks all
     /// the updates that the datastore has on record again.
     /// Reports error if the number of updated rows does not meet the threshold.
+    ///
+    /// The rows selected are counted over the same requests, so that updates
+    /// made without first reading their rows are reported too.
     fn verify_updates_count(
         &self,
-        url: &str,
+        request: &Request,
         table_name: &str,
         concurrency: u32,
         repetitions: u32,
         expected_updates: u32,
         messages: &mut Messages,
     ) {
-        let all_rows_updated_before_count = self
+        let all_rows_updated_before_count = match self
             .database_verifier
-            .get_count_of_rows_updated_for_table(table_name, 1);
+            .get_count_of_rows_updated_for_table(table_name, 1)
+        {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
+        let all_rows_selected_before_count = self
+            .database_verifier
+            .get_count_of_rows_selected_for_table(table_name, 1);
+
+        if self.database_verifier.issue_multi_query_requests(
+            request,
+            concurrency,
+            repetitions,
+            messages,
+        ) {
+            return;
+        }
 
-        self.database_verifier
-            .issue_multi_query_requests(url, concurrency, repetitions, messages);
+        let all_rows_updated_after_count = match self
+            .database_verifier
+            .get_count_of_rows_updated_for_table(table_name, 1)
+        {
+            Ok(count) => count,
+            Err(e) => {
+                report_counting_error(e, messages);
+                return;
+            }
+        };
 
-        let all_rows_updated_after_count = self
+        let all_rows_selected_after_count = self
             .database_verifier
-            .get_count_of_rows_updated_for_table(table_name, 1);
+            .get_count_of_rows_selected_for_table(table_name, 1);
 
         let updated = all_rows_updated_after_count - all_rows_updated_before_count;
+        // Failing to count selects is only a gap in this one check; the
+        // update count is reported regardless.
+        if let (Ok(before), Ok(after)) = (
+            all_rows_selected_before_count,
+            all_rows_selected_after_count,
+        ) {
+            report_blind_updates(after.saturating_sub(before), updated, messages);
+        }
         // Note: Some database implementations are less accurate (though still
         // precise) than others, and sometimes over-report rows updated. We do
         // not warn because it would just be noisy over something out of the
@@ -156,39 +296,42 @@ impl Updates {
     }
 
     /// Queries all the data in the `World` table, runs an example update
-    /// set of requests, then queries all the data in the `World` table again.
-    /// Reports error if the number of updated rows does not meet the threshold.
+    /// set of requests, then queries all the data in the `World` table again,
+    /// `UPDATE_DETECTION_BURSTS` times over.
+    /// Reports error if the number of distinct rows updated across the bursts
+    /// does not meet the threshold.
     fn verify_updates(
         &self,
-        url: &str,
+        request: &Request,
         concurrency: u32,
         repetitions: u32,
         messages: &mut Messages,
     ) {
-        let expected_updates = concurrency * repetitions;
+        // The distinct rows are accumulated over every burst, so each raises
+        // the threshold alike.
+        let expected_updates = concurrency * repetitions * UPDATE_DETECTION_BURSTS;
         // Note: we do this outside of `verify_updates_count` so we do not mess
         // up the counting. Down here, we no longer care about the query/select
         // counts, we only want to see that an appropriate number of updates
         // occurred on the underlying data.
 
-        let worlds_before = self.database_verifier.get_all_from_world_table();
-
-        self.database_verifier
-            .issue_multi_query_requests(url, concurrency, 1, messages);
-
-        let worlds_after = self.database_verifier.get_all_from_world_table();
-
-        let mut updates = 0;
-        for index in 0..worlds_before.len() {
-            if worlds_before.get(&(index as i32)).is_some()
-                && worlds_after.get(&(index as i32)).is_some()
-                && worlds_before.get(&(index as i32)).unwrap()
-                    != worlds_after.get(&(index as i32)).unwrap()
+        let mut worlds_before = self.database_verifier.get_all_from_world_table(messages);
+        let mut updated_ids = HashSet::new();
+        for _ in 0..UPDATE_DETECTION_BURSTS {
+            if self
+                .database_verifier
+                .issue_multi_query_requests(request, concurrency, 1, messages)
             {
-                updates += 1;
+                return;
             }
+
+            let worlds_after = self.database_verifier.get_all_from_world_table(messages);
+            updated_ids.extend(updated_world_ids(&worlds_before, &worlds_after));
+            worlds_before = worlds_after;
         }
 
+        let updates = updated_ids.len() as i32;
+
         if updates == 0 {
             messages.error("No items were updated in the database.", "No Updates");
         } else if updates <= (expected_updates as f32 * 0.90) as i32 {
@@ -210,7 +353,7 @@ impl Updates {
             "-H",
             "Host: tfb-server",
             "-H",
-            "Accept: application/json,text/html;q=0.9,application/xhtml+xml;q=0.9,application/xml;q=0.8,*/*;q=0.7",
+            &format!("Accept: {}", ContentType::Json.benchmark_accept()),
             "-H",
             "Connection: keep-alive",
             "--latency",
@@ -223,6 +366,236 @@ impl Updates {
             "-t",
             &format!("{}", min(concurrency, num_cpus::get() as u32)),
             url,
-        ].iter().map(|item| item.to_string()).collect()
+        ]
+        .iter()
+        .map(|item| item.to_string())
+        .collect()
+    }
+}
+
+//
+// PRIVATES
+//
+
+/// Warns when markedly fewer rows were `selected` than `updated` over the
+/// same requests; each row must be read before it is updated, rather than
+/// updated blindly.
+fn report_blind_updates(selected: u32, updated: u32, messages: &mut Messages) {
+    if (selected as f32) < updated as f32 * 0.90 {
+        messages.warning(
+            format!(
+                "Only {} rows were selected for {} rows updated; each row should be read before it is updated.",
+                selected, updated
+            ),
+            "Blind Updates",
+        );
+    }
+}
+
+/// Gets the `id`s in `before` whose `randomNumber` differs in `after`.
+///
+/// Note: the ids are whatever the table holds (e.g. `1..=10000`, or
+/// `0..=9999` per `WORLD_ID_BASE`), so they are walked as given rather than
+/// assumed to be contiguous from `0`.
+fn updated_world_ids(before: &HashMap<i32, i32>, after: &HashMap<i32, i32>) -> HashSet<i32> {
+    before
+        .iter()
+        .filter(|(id, random_number)| matches!(after.get(id), Some(updated) if updated != *random_number))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+//
+// TESTS
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::config::UpdateMethod;
+    use crate::database::stub::Stub;
+    use crate::request::{ContentType, HttpMethod, Request};
+    use crate::test_type::query::updates::{report_blind_updates, updated_world_ids, Updates};
+    use crate::test_type::Executor;
+    use crate::verification::Messages;
+    use std::collections::HashMap;
+
+    fn updates() -> Updates {
+        Updates {
+            concurrency_levels: vec![16],
+            repetitions: 2,
+            database_verifier: Box::new(Stub::default()),
+            update_method: UpdateMethod::Get,
+            update_body_template: r#"{"queries":"{queries}"}"#.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_should_pass_on_matching_updates_length() {
+        let mut messages = Messages::default();
+        let expected = updates().verify_updates_length(
+            "2",
+            "[{\"id\":1,\"randomNumber\":2},{\"id\":3,\"randomNumber\":4}]",
+            1,
+            500,
+            &mut messages,
+        );
+        assert_eq!(expected, 2);
+        assert!(messages.errors.is_empty());
+    }
+
+    #[test]
+    fn it_should_error_on_mismatched_updates_length() {
+        let mut messages = Messages::default();
+        updates().verify_updates_length(
+            "2",
+            "[{\"id\":1,\"randomNumber\":2}]",
+            1,
+            500,
+            &mut messages,
+        );
+        assert!(messages
+            .errors
+            .first()
+            .unwrap()
+            .message
+            .contains("JSON array length of 1 < expected length of 2"));
+    }
+
+    #[test]
+    fn it_should_error_on_object_for_multiple_updates() {
+        let mut messages = Messages::default();
+        updates().verify_updates_length(
+            "2",
+            "{\"id\":1,\"randomNumber\":2}",
+            1,
+            500,
+            &mut messages,
+        );
+        assert!(messages
+            .errors
+            .first()
+            .unwrap()
+            .message
+            .contains("array of expected length 2"));
+    }
+
+    #[test]
+    fn it_should_append_queries_to_get_update_url() {
+        let request = updates().update_request("http://tfb-server:8080/updates?queries=", "20");
+
+        assert_eq!(
+            request,
+            Request::get("http://tfb-server:8080/updates?queries=20")
+                .accepting(ContentType::Json.accept())
+        );
+    }
+
+    #[test]
+    fn it_should_post_queries_in_update_body() {
+        let updates = Updates {
+            update_method: UpdateMethod::Post,
+            ..updates()
+        };
+        let request = updates.update_request("http://tfb-server:8080/updates", "20");
+
+        assert_eq!(
+            request,
+            Request::with_body(
+                "http://tfb-server:8080/updates",
+                HttpMethod::Post,
+                br#"{"queries":"20"}"#.to_vec()
+            )
+            .accepting(ContentType::Json.accept())
+        );
+    }
+
+    #[test]
+    fn it_should_count_updated_zero_based_worlds() {
+        let before: HashMap<i32, i32> = (0..10_000).map(|id| (id, 1)).collect();
+        let mut after = before.clone();
+        after.insert(0, 2);
+        after.insert(9_999, 2);
+
+        assert_eq!(updated_world_ids(&before, &after).len(), 2);
+    }
+
+    #[test]
+    fn it_should_count_updated_one_based_worlds() {
+        let before: HashMap<i32, i32> = (1..=10_000).map(|id| (id, 1)).collect();
+        let mut after = before.clone();
+        after.insert(1, 2);
+        after.insert(5_000, 2);
+
+        assert_eq!(updated_world_ids(&before, &after).len(), 2);
+    }
+
+    #[test]
+    fn it_should_count_update_to_last_one_based_world() {
+        let before: HashMap<i32, i32> = (1..=10_000).map(|id| (id, 1)).collect();
+        let mut after = before.clone();
+        after.insert(10_000, 2);
+
+        assert_eq!(updated_world_ids(&before, &after).len(), 1);
+    }
+
+    #[test]
+    fn it_should_count_updated_non_contiguous_worlds() {
+        let before: HashMap<i32, i32> = [(3, 1), (17, 1), (9_001, 1)].into_iter().collect();
+        let mut after = before.clone();
+        after.insert(17, 2);
+        after.insert(9_001, 2);
+
+        let updated = updated_world_ids(&before, &after);
+        assert_eq!(updated.len(), 2);
+        assert!(updated.contains(&17) && updated.contains(&9_001));
+    }
+
+    #[test]
+    fn it_should_not_count_unchanged_or_missing_worlds() {
+        let before: HashMap<i32, i32> = (1..=10_000).map(|id| (id, 1)).collect();
+        let mut after = before.clone();
+        after.remove(&10_000);
+        after.insert(0, 2);
+
+        assert_eq!(updated_world_ids(&before, &after).len(), 0);
+    }
+
+    #[test]
+    fn it_should_expect_twenty_updates_per_request() {
+        let counts = updates().expected_counts().unwrap();
+        assert_eq!(counts.concurrency, 16);
+        assert_eq!(counts.repetitions, 2);
+        assert_eq!(counts.expected_rows, 20 * 2 * 16);
+        assert_eq!(counts.expected_updates, Some(20 * 2 * 16));
+        assert_eq!(counts.expected_queries, 20 * 2 * 16 / 20);
+    }
+
+    #[test]
+    fn it_should_warn_about_updates_without_selects() {
+        let mut messages = Messages::default();
+        report_blind_updates(640, 640, &mut messages);
+        report_blind_updates(600, 640, &mut messages);
+        assert!(messages.warnings.is_empty());
+
+        report_blind_updates(32, 640, &mut messages);
+        assert_eq!(messages.warnings.len(), 1);
+        assert!(messages.warnings[0]
+            .message
+            .starts_with("Only 32 rows were selected for 640 rows updated"));
+    }
+
+    #[test]
+    fn it_should_accumulate_distinct_updates_across_bursts() {
+        let first: HashMap<i32, i32> = (1..=10).map(|id| (id, 1)).collect();
+        let mut second = first.clone();
+        second.insert(3, 2);
+        second.insert(7, 2);
+        let mut third = second.clone();
+        third.insert(7, 3);
+        third.insert(9, 2);
+
+        let mut updated_ids = updated_world_ids(&first, &second);
+        updated_ids.extend(updated_world_ids(&second, &third));
+        assert_eq!(updated_ids.len(), 3);
     }
 }
@@ -1,5 +1,4 @@
 use crate::benchmark::BenchmarkCommands;
-use crate::database::DatabaseInterface;
 use crate::error::VerifierResult;
 use crate::test_type::Executor;
 use crate::verification::Messages;
@@ -8,17 +7,21 @@ use std::time::Duration;
 
 pub struct Unknown {
     pub(crate) test_type: String,
-    pub database_verifier: Box<dyn DatabaseInterface>,
 }
 impl Executor for Unknown {
-    fn wait_for_database_to_be_available(&self) {
-        self.database_verifier.wait_for_database_to_be_available();
+    fn name(&self) -> &'static str {
+        "unknown"
     }
 
     fn retrieve_benchmark_commands(&self, _url: &str) -> VerifierResult<BenchmarkCommands> {
         Ok(BenchmarkCommands::default())
     }
 
+    /// There is no endpoint worth requesting for an unknown test type.
+    fn smoke_test(&self, _url: &str) -> VerifierResult<()> {
+        Ok(())
+    }
+
     fn verify(&self, url: &str) -> VerifierResult<Messages> {
         let mut messages = Messages::new(url);
 
@@ -5,8 +5,11 @@
 //! order to pass data about the state of the verification, we serialize
 //! messages specifically for the consumption by the toolset that will not be
 //! printed.
+use crate::config;
+use crate::logger::{log, log_to_file, LogLevel, LogOptions};
 use colored::Colorize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Warning {
@@ -14,6 +17,8 @@ pub struct Warning {
     pub url: String,
     pub headers: String,
     pub message: String,
+    /// The name of the test type which reported it; empty until tagged.
+    pub test_type: String,
 }
 #[derive(Clone)]
 pub struct Error {
@@ -21,6 +26,8 @@ pub struct Error {
     pub url: String,
     pub headers: String,
     pub message: String,
+    /// The name of the test type which reported it; empty until tagged.
+    pub test_type: String,
 }
 
 /// The mechanism for message interfacing with the calling `TFBToolset`. Every
@@ -35,18 +42,32 @@ pub struct Error {
 pub struct Messages {
     pub warnings: Vec<Warning>,
     pub errors: Vec<Error>,
+    /// Bytes returned beyond the minimum response, per size-sensitive check.
+    pub extra_bytes: Vec<(String, usize)>,
+    /// The total times of the requests sent while counting database queries.
+    pub latencies: Vec<Duration>,
     url: String,
     body: String,
     headers: String,
+    test_type: String,
+    /// The short messages captured while `deduplicated`, if it is running.
+    seen_short_messages: Option<HashSet<String>>,
+    /// When set, messages are only captured; see `silent`.
+    silent: bool,
 }
 impl Messages {
     pub fn default() -> Self {
         Self {
             warnings: Vec::new(),
             errors: Vec::new(),
+            extra_bytes: Vec::new(),
+            latencies: Vec::new(),
             url: "".to_string(),
             body: "".to_string(),
             headers: "".to_string(),
+            test_type: "".to_string(),
+            seen_short_messages: None,
+            silent: false,
         }
     }
 
@@ -54,9 +75,24 @@ impl Messages {
         Self {
             warnings: Vec::new(),
             errors: Vec::new(),
+            extra_bytes: Vec::new(),
+            latencies: Vec::new(),
             url: url.to_string(),
             body: "".to_string(),
             headers: "".to_string(),
+            test_type: "".to_string(),
+            seen_short_messages: None,
+            silent: false,
+        }
+    }
+
+    /// Gets messages which capture errors and warnings without sending or
+    /// logging them; e.g. to tell whether one of many samples is valid before
+    /// reporting on them all at once.
+    pub fn silent() -> Self {
+        Self {
+            silent: true,
+            ..Self::default()
         }
     }
 
@@ -68,19 +104,42 @@ impl Messages {
         self.headers = get_headers_as_string(headers);
     }
 
+    /// Tags these messages, and those captured hereafter, as reported by the
+    /// `test_type`; messages already tagged, e.g. when merged, keep theirs.
+    pub fn test_type(&mut self, test_type: &str) {
+        self.test_type = test_type.to_string();
+        for warning in self.warnings.iter_mut() {
+            if warning.test_type.is_empty() {
+                warning.test_type = test_type.to_string();
+            }
+        }
+        for error in self.errors.iter_mut() {
+            if error.test_type.is_empty() {
+                error.test_type = test_type.to_string();
+            }
+        }
+    }
+
     /// Captures and sends an error message.
     pub fn error<T, F>(&mut self, message: T, short_message: F)
     where
         T: std::fmt::Display,
         F: std::fmt::Display,
     {
-        send_error(&message, &short_message);
+        if self.already_seen(&short_message) {
+            return;
+        }
+        if !self.silent {
+            send_error(&message, &short_message);
+            log_to_file(&format!("ERROR {}", message));
+        }
 
         let error = Error {
             url: self.url.clone(),
             body: self.body.clone(),
             headers: self.headers.clone(),
             message: message.to_string(),
+            test_type: self.test_type.clone(),
         };
 
         self.errors.push(error);
@@ -92,24 +151,120 @@ impl Messages {
         T: std::fmt::Display,
         F: std::fmt::Display,
     {
-        send_warning(&message, &short_message);
+        if self.already_seen(&short_message) {
+            return;
+        }
+        if !self.silent {
+            send_warning(&message, &short_message);
+            log_to_file(&format!("WARN {}", message));
+        }
 
         let warning = Warning {
             body: self.body.clone(),
             url: self.url.clone(),
             headers: self.headers.clone(),
             message: message.to_string(),
+            test_type: self.test_type.clone(),
         };
         self.warnings.push(warning);
     }
 
+    /// Runs `verify`, capturing only the first error or warning of each short
+    /// message it reports; e.g. so that the same problem with each of 500
+    /// objects is reported once.
+    pub fn deduplicated<V>(&mut self, verify: V)
+    where
+        V: FnOnce(&mut Messages),
+    {
+        self.seen_short_messages = Some(HashSet::new());
+        verify(self);
+        self.seen_short_messages = None;
+    }
+
+    /// Whether a message with `short_message` was already captured while
+    /// `deduplicated`; records it if not.
+    fn already_seen<F>(&mut self, short_message: &F) -> bool
+    where
+        F: std::fmt::Display,
+    {
+        match &mut self.seen_short_messages {
+            Some(seen) => !seen.insert(short_message.to_string()),
+            None => false,
+        }
+    }
+
+    /// Folds the results of the independent verification `other` into these;
+    /// its errors and warnings keep the `url`, `body` and `headers` they were
+    /// reported with, rather than taking on this context.
+    pub fn merge(&mut self, other: Messages) {
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+        self.extra_bytes.extend(other.extra_bytes);
+        self.latencies.extend(other.latencies);
+    }
+
+    /// Records how many bytes the response to `check` carried beyond its
+    /// minimum, for the efficiency summary. Zero is recorded too, so that the
+    /// summary shows every check which was measured.
+    pub fn extra_bytes(&mut self, check: &str, bytes: usize) {
+        self.extra_bytes.push((check.to_string(), bytes));
+    }
+
+    /// Summarizes the recorded extra bytes on a single line, or `None` if no
+    /// size-sensitive check ran.
+    pub fn extra_bytes_summary(&self) -> Option<String> {
+        if self.extra_bytes.is_empty() {
+            return None;
+        }
+        let checks = self
+            .extra_bytes
+            .iter()
+            .map(|(check, bytes)| format!("{} +{}", check, bytes))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let total: usize = self.extra_bytes.iter().map(|(_, bytes)| bytes).sum();
+
+        Some(format!("Bytes over minimum: {} (total +{})", checks, total))
+    }
+
+    /// Records the `latencies` of requests sent during verification; they are
+    /// diagnostic only and never fail verification.
+    pub fn latencies(&mut self, latencies: Vec<Duration>) {
+        self.latencies.extend(latencies);
+    }
+
+    /// Summarizes the recorded latencies on a single line, or `None` if no
+    /// requests were timed.
+    pub fn latency_summary(&self) -> Option<String> {
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        let millis = |duration: &Duration| format!("{:.1}ms", duration.as_secs_f64() * 1_000.0);
+
+        Some(format!(
+            "Latencies of {} requests: min {}, median {}, max {}",
+            latencies.len(),
+            millis(latencies.first()?),
+            millis(&latencies[latencies.len() / 2]),
+            millis(latencies.last()?)
+        ))
+    }
+
     /// Prints out the results and if there are no errors, sends the passed message.
+    ///
+    /// Note: under `config::strict`, warnings are printed as errors since
+    /// they fail verification.
     pub fn output_verification_results(&self) {
         if self.errors.is_empty() && self.warnings.is_empty() {
             println!("   {}", "PASS".green());
         } else {
+            let pretty = config::pretty_body();
+            let warning_label = if config::strict() {
+                "ERROR".red()
+            } else {
+                "WARN".yellow()
+            };
             for warning in &self.warnings {
-                println!("   {}", "WARN".yellow());
+                println!("   {}{}", warning_label, tag(&warning.test_type));
                 println!("     {}", warning.message);
                 println!("     See https://github.com/TechEmpower/FrameworkBenchmarks/wiki/Project-Information-Framework-Tests-Overview#specific-test-requirements");
                 if !warning.url.is_empty() {
@@ -119,11 +274,11 @@ impl Messages {
                     println!("{}", warning.headers);
                 }
                 if !warning.body.is_empty() {
-                    println!("{}", warning.body);
+                    println!("{}", display_body(&warning.body, pretty));
                 }
             }
             for error in &self.errors {
-                println!("   {}", "ERROR".red());
+                println!("   {}{}", "ERROR".red(), tag(&error.test_type));
                 println!("     {}", error.message);
                 println!("     See https://github.com/TechEmpower/FrameworkBenchmarks/wiki/Project-Information-Framework-Tests-Overview#specific-test-requirements");
                 if !error.url.is_empty() {
@@ -133,10 +288,30 @@ impl Messages {
                     println!("{}", error.headers);
                 }
                 if !error.body.is_empty() {
-                    println!("{}", error.body);
+                    println!("{}", display_body(&error.body, pretty));
                 }
             }
         }
+        if let Some(summary) = self.extra_bytes_summary() {
+            log(
+                summary.normal(),
+                LogOptions {
+                    border: None,
+                    border_bottom: None,
+                    level: LogLevel::Info,
+                },
+            );
+        }
+        if let Some(summary) = self.latency_summary() {
+            log(
+                summary.normal(),
+                LogOptions {
+                    border: None,
+                    border_bottom: None,
+                    level: LogLevel::Debug,
+                },
+            );
+        }
     }
 }
 
@@ -144,6 +319,33 @@ impl Messages {
 // PRIVATES
 //
 
+/// Gets `body` for display; when `pretty` and it is JSON, pretty-printed with
+/// numbered lines, so that e.g. the bad row of a long array can be found.
+fn display_body(body: &str, pretty: bool) -> String {
+    if !pretty {
+        return body.to_string();
+    }
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => serde_json::to_string_pretty(&json)
+            .unwrap()
+            .lines()
+            .enumerate()
+            .map(|(index, line)| format!("{:>4} | {}", index + 1, line))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Err(_) => body.to_string(),
+    }
+}
+
+/// Gets the suffix naming `test_type` after a message's label, if tagged.
+fn tag(test_type: &str) -> String {
+    if test_type.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", test_type)
+    }
+}
+
 fn get_headers_as_string(headers: &HashMap<String, String>) -> String {
     let mut header_str = String::new();
     for entry in headers {
@@ -192,8 +394,9 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::verification::{send_error, send_warning};
+    use crate::verification::{display_body, send_error, send_warning, Messages};
     use serde_json::Value;
+    use std::time::Duration;
 
     #[test]
     fn it_can_serialize_a_warning_verification() {
@@ -212,4 +415,118 @@ mod tests {
         assert_eq!(json["error"]["message"], "Incorrect response body");
         assert_eq!(json["error"]["short_message"], "Incorrect response");
     }
+
+    #[test]
+    fn it_should_merge_messages_with_their_context() {
+        let mut json = Messages::new("http://tfb-server:8080/json");
+        json.body("{}");
+        json.error("Key 'message' is missing", "Missing Key");
+        json.warning("Chunked response", "Chunked Response");
+
+        let mut jsonp = Messages::new("http://tfb-server:8080/jsonp");
+        jsonp.body("callback({})");
+        jsonp.error("Invalid JSON", "Invalid JSON");
+
+        json.merge(jsonp);
+        assert_eq!(json.errors.len(), 2);
+        assert_eq!(json.warnings.len(), 1);
+        assert_eq!(json.errors[0].url, "http://tfb-server:8080/json");
+        assert_eq!(json.errors[0].body, "{}");
+        assert_eq!(json.errors[1].url, "http://tfb-server:8080/jsonp");
+        assert_eq!(json.errors[1].body, "callback({})");
+        assert_eq!(json.errors[1].message, "Invalid JSON");
+    }
+
+    #[test]
+    fn it_should_tag_messages_with_their_test_type() {
+        let mut jsonp = Messages::new("http://tfb-server:8080/jsonp");
+        jsonp.error("Invalid JSON", "Invalid JSON");
+        jsonp.test_type("jsonp");
+
+        let mut json = Messages::new("http://tfb-server:8080/json");
+        json.warning("Chunked response", "Chunked Response");
+        json.merge(jsonp);
+        json.test_type("json");
+        json.error("Key 'message' is missing", "Missing Key");
+
+        assert_eq!(json.warnings[0].test_type, "json");
+        assert_eq!(json.errors[0].test_type, "jsonp");
+        assert_eq!(json.errors[1].test_type, "json");
+    }
+
+    #[test]
+    fn it_should_summarize_latencies() {
+        let mut messages = Messages::default();
+        assert!(messages.latency_summary().is_none());
+
+        messages.latencies(vec![
+            Duration::from_millis(20),
+            Duration::from_micros(1_500),
+            Duration::from_millis(3),
+        ]);
+        assert_eq!(
+            messages.latency_summary().unwrap(),
+            "Latencies of 3 requests: min 1.5ms, median 3.0ms, max 20.0ms"
+        );
+    }
+
+    #[test]
+    fn it_should_summarize_extra_bytes() {
+        let mut messages = Messages::default();
+        assert!(messages.extra_bytes_summary().is_none());
+
+        messages.extra_bytes("json", 5);
+        messages.extra_bytes("plaintext", 0);
+        messages.extra_bytes("fortune", 12);
+        assert_eq!(
+            messages.extra_bytes_summary().unwrap(),
+            "Bytes over minimum: json +5, plaintext +0, fortune +12 (total +17)"
+        );
+    }
+
+    #[test]
+    fn it_should_capture_each_short_message_once_while_deduplicated() {
+        let mut messages = Messages::default();
+        messages.deduplicated(|messages| {
+            for id in 0..3 {
+                messages.error(format!("Bad id {}", id), "Invalid id");
+                messages.warning(format!("Odd id {}", id), "Odd id");
+            }
+            messages.error("Bad randomNumber", "Invalid randomNumber");
+        });
+        messages.error("Bad id 3", "Invalid id");
+
+        let errors: Vec<&str> = messages.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors, vec!["Bad id 0", "Bad randomNumber", "Bad id 3"]);
+        assert_eq!(messages.warnings.len(), 1);
+    }
+
+    #[test]
+    fn it_should_capture_messages_while_silent() {
+        let mut messages = Messages::silent();
+        messages.error("Bad id 0", "Invalid id");
+        messages.warning("Odd id 0", "Odd id");
+
+        assert_eq!(messages.errors.len(), 1);
+        assert_eq!(messages.warnings.len(), 1);
+    }
+
+    #[test]
+    fn it_should_number_the_lines_of_a_pretty_json_body() {
+        let body = r#"[{"id":1,"randomNumber":2}]"#;
+        assert_eq!(display_body(body, false), body);
+        assert_eq!(
+            display_body(body, true),
+            [
+                "   1 | [",
+                "   2 |   {",
+                "   3 |     \"id\": 1,",
+                "   4 |     \"randomNumber\": 2",
+                "   5 |   }",
+                "   6 | ]",
+            ]
+            .join("\n")
+        );
+        assert_eq!(display_body("Hello, World!", true), "Hello, World!");
+    }
 }